@@ -0,0 +1,48 @@
+// Arbitrary roms through `load_bytes` and `cycle`, `cargo fuzz run
+// load_and_cycle` from the repo root. Errors are fine, panics and
+// crashes aren't.
+#![no_main]
+#![allow(dead_code, unused_imports)]
+
+use libfuzzer_sys::fuzz_target;
+
+// The emulator is a binary, so the interpreter and what it pulls in are
+// compiled straight from the source tree
+#[path = "../../src/app/chip8.rs"]
+mod chip8;
+#[path = "../../src/app/filter.rs"]
+mod filter;
+#[path = "../../src/app/font.rs"]
+mod font;
+#[cfg(target_arch = "x86_64")]
+#[path = "../../src/app/jit.rs"]
+mod jit;
+#[path = "../../src/app/render.rs"]
+mod render;
+#[path = "../../src/shared/mod.rs"]
+mod shared;
+
+use chip8::Chip8;
+
+/// Enough for any rom to loop or run off somewhere
+const MAX_CYCLES: usize = 10_000;
+
+fuzz_target!(|data: &[u8]| {
+    // the first two bytes are the keys held down, the rest is the rom
+    let (keys, rom) = match data {
+        [a, b, rom @ ..] => (u16::from_le_bytes([*a, *b]), rom),
+        _ => (0, data),
+    };
+    let mut chip8 = Chip8::new();
+    if chip8.load_bytes(rom).is_err() {
+        return;
+    }
+    for (i, key) in chip8.keys.iter_mut().enumerate() {
+        *key = keys & (1 << i) != 0;
+    }
+    for _ in 0..MAX_CYCLES {
+        if chip8.cycle().is_err() {
+            break;
+        }
+    }
+});
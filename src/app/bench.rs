@@ -0,0 +1,52 @@
+use std::time::Instant;
+
+use super::chip8::{Backend, Chip8};
+use super::options::Options;
+
+// Runs the rom headless for `cycles` cycles on every backend, reloading it
+// whenever the core halts, and prints cycles per second for each.
+pub fn run(opts: &Options, cycles: u64) -> std::io::Result<()> {
+    let rom = std::fs::read(&opts.rom_path)?;
+    let mut baseline = None;
+
+    for backend in Backend::ALL {
+        let mut chip8 = new_chip8(*backend, &rom)?;
+        let mut halts = 0;
+
+        let start = Instant::now();
+        let mut left = cycles;
+        while left > 0 {
+            // in chunks so halts don't throw away too much
+            match chip8.run(left.min(4096)) {
+                Ok(ran) => left -= ran,
+                Err(_) => {
+                    left -= 1;
+                    halts += 1;
+                    chip8 = new_chip8(*backend, &rom)?;
+                }
+            }
+        }
+        let secs = start.elapsed().as_secs_f64();
+
+        let per_sec = cycles as f64 / secs;
+        let speedup = per_sec / *baseline.get_or_insert(per_sec);
+        println!(
+            "{:<12} {:>12.0} cycles/s {:>6.2}x ({:.3}s, {} halts)",
+            backend.name(),
+            per_sec,
+            speedup,
+            secs,
+            halts
+        );
+    }
+    Ok(())
+}
+
+fn new_chip8(backend: Backend, rom: &[u8]) -> std::io::Result<Chip8> {
+    let mut chip8 = Chip8::new();
+    chip8.set_backend(backend)?;
+    chip8
+        .load_bytes(rom)
+        .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))?;
+    Ok(chip8)
+}
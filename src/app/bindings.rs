@@ -0,0 +1,308 @@
+// What keys do. Keys are picked by keysym (what's printed on them, from
+// the compositor's keymap) or by scancode (where they are, whatever the
+// layout). A binding file has one `<key> = <action>` per line, `#` starts a
+// comment:
+//
+//     # keypad 0 on X, wherever the layout puts it
+//     x = 0
+//     # keypad 1 on the key left of W on any layout
+//     scancode:16 = 1
+//     Escape = quit
+//     # no fast forward
+//     scancode:15 = none
+//
+// Lines in the file are added on top of the preset, `none` drops a binding.
+use std::collections::HashMap;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    /// One of the 16 hex keys
+    Key(u8),
+    Quit,
+    Pause,
+    /// One frame while paused
+    Advance,
+    Reset,
+    Slower,
+    Faster,
+    /// While held
+    FastForward,
+    Stats,
+    Palette,
+    Maximize,
+    Fullscreen,
+    /// PNG in the pictures directory
+    Screenshot,
+    /// PNG on the clipboard
+    CopyScreenshot,
+    /// Start / stop
+    Record,
+}
+
+impl Action {
+    pub const ALL: &[Action] = &[
+        Self::Quit,
+        Self::Pause,
+        Self::Advance,
+        Self::Reset,
+        Self::Slower,
+        Self::Faster,
+        Self::FastForward,
+        Self::Stats,
+        Self::Palette,
+        Self::Maximize,
+        Self::Fullscreen,
+        Self::Screenshot,
+        Self::CopyScreenshot,
+        Self::Record,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Key(_) => "key",
+            Self::Quit => "quit",
+            Self::Pause => "pause",
+            Self::Advance => "advance",
+            Self::Reset => "reset",
+            Self::Slower => "slower",
+            Self::Faster => "faster",
+            Self::FastForward => "fast-forward",
+            Self::Stats => "stats",
+            Self::Palette => "palette",
+            Self::Maximize => "maximize",
+            Self::Fullscreen => "fullscreen",
+            Self::Screenshot => "screenshot",
+            Self::CopyScreenshot => "copy-screenshot",
+            Self::Record => "record",
+        }
+    }
+
+    // A single hex digit or an action name
+    pub fn parse(s: &str) -> Result<Self, String> {
+        if s.len() == 1
+            && let Ok(key) = u8::from_str_radix(s, 16)
+        {
+            return Ok(Self::Key(key));
+        }
+        Self::ALL
+            .iter()
+            .find(|a| a.name() == s)
+            .copied()
+            .ok_or_else(|| format!("Unknown action '{}'", s))
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Preset {
+    /// 1234/QWER/ASDF/ZXCV by position, works on any layout
+    #[default]
+    Qwerty,
+    /// Digits on the numpad digits, A-F on the keys around them
+    Numpad,
+    /// 1234/AZER/QSDF/WXCV by what's printed on the keys
+    Azerty,
+}
+
+impl Preset {
+    pub const ALL: &[Preset] = &[Self::Qwerty, Self::Numpad, Self::Azerty];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Qwerty => "qwerty",
+            Self::Numpad => "numpad",
+            Self::Azerty => "azerty",
+        }
+    }
+
+    fn bindings(&self) -> &'static str {
+        match self {
+            Self::Qwerty => QWERTY,
+            Self::Numpad => NUMPAD,
+            Self::Azerty => AZERTY,
+        }
+    }
+}
+
+// Emulator controls, the same in every preset
+const CONTROLS: &str = "
+scancode:1 = quit
+scancode:25 = pause
+scancode:49 = advance
+scancode:14 = reset
+scancode:12 = slower
+scancode:13 = faster
+scancode:15 = fast-forward
+scancode:61 = stats
+scancode:67 = palette
+scancode:68 = maximize
+scancode:87 = fullscreen
+scancode:88 = screenshot
+scancode:66 = copy-screenshot
+scancode:65 = record
+";
+
+const QWERTY: &str = "
+scancode:2 = 1
+scancode:3 = 2
+scancode:4 = 3
+scancode:5 = c
+scancode:16 = 4
+scancode:17 = 5
+scancode:18 = 6
+scancode:19 = d
+scancode:30 = 7
+scancode:31 = 8
+scancode:32 = 9
+scancode:33 = e
+scancode:44 = a
+scancode:45 = 0
+scancode:46 = b
+scancode:47 = f
+";
+
+// The numpad sends different keysyms with num lock on and off, scancodes
+// don't care
+const NUMPAD: &str = "
+scancode:82 = 0
+scancode:79 = 1
+scancode:80 = 2
+scancode:81 = 3
+scancode:75 = 4
+scancode:76 = 5
+scancode:77 = 6
+scancode:71 = 7
+scancode:72 = 8
+scancode:73 = 9
+scancode:98 = a
+scancode:55 = b
+scancode:74 = c
+scancode:78 = d
+scancode:96 = e
+scancode:83 = f
+";
+
+const AZERTY: &str = "
+ampersand = 1
+eacute = 2
+quotedbl = 3
+apostrophe = c
+a = 4
+z = 5
+e = 6
+r = d
+q = 7
+s = 8
+d = 9
+f = e
+w = a
+x = 0
+c = b
+v = f
+";
+
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub(crate) struct Bindings {
+    scancodes: HashMap<u32, Action>,
+    keysyms: HashMap<String, Action>,
+}
+
+impl Bindings {
+    pub fn preset(preset: Preset) -> Self {
+        let mut bindings = Self::default();
+        // presets are known good
+        bindings.apply(CONTROLS).unwrap();
+        bindings.apply(preset.bindings()).unwrap();
+        bindings
+    }
+
+    // Adds the lines of a binding file, on error nothing is changed
+    pub fn apply(&mut self, text: &str) -> Result<(), String> {
+        let mut next = self.clone();
+        for (i, line) in text.lines().enumerate() {
+            let line = line.split_once('#').map_or(line, |(l, _)| l).trim();
+            if line.is_empty() {
+                continue;
+            }
+            let (key, action) = line
+                .split_once('=')
+                .map(|(k, a)| (k.trim(), a.trim()))
+                .ok_or_else(|| format!("Line {}: expected '<key> = <action>'", i + 1))?;
+            let action = match action {
+                "none" => None,
+                _ => Some(Action::parse(action).map_err(|e| format!("Line {}: {}", i + 1, e))?),
+            };
+            match key.strip_prefix("scancode:") {
+                Some(code) => {
+                    let code = code
+                        .trim()
+                        .parse()
+                        .map_err(|_| format!("Line {}: '{}' is not a scancode", i + 1, code))?;
+                    match action {
+                        Some(action) => next.scancodes.insert(code, action),
+                        None => next.scancodes.remove(&code),
+                    };
+                }
+                None => {
+                    match action {
+                        Some(action) => next.keysyms.insert(key.to_string(), action),
+                        None => next.keysyms.remove(key),
+                    };
+                }
+            }
+        }
+        *self = next;
+        Ok(())
+    }
+
+    pub fn load(&mut self, path: &str) -> std::io::Result<()> {
+        let data = std::fs::read_to_string(path)?;
+        self.apply(&data).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+
+    // Keysym bindings win, they're the more specific ones in a file
+    pub fn get(&self, scancode: u32, keysym: Option<&str>) -> Option<Action> {
+        keysym
+            .and_then(|k| self.keysyms.get(k))
+            .or_else(|| self.scancodes.get(&scancode))
+            .copied()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn presets_cover_the_keypad() {
+        for preset in Preset::ALL {
+            let bindings = Bindings::preset(*preset);
+            let mut keys: Vec<u8> = bindings
+                .scancodes
+                .values()
+                .chain(bindings.keysyms.values())
+                .filter_map(|a| match a {
+                    Action::Key(k) => Some(*k),
+                    _ => None,
+                })
+                .collect();
+            keys.sort();
+            assert_eq!(keys, (0..16).collect::<Vec<_>>(), "{}", preset.name());
+        }
+    }
+
+    #[test]
+    fn file_lines_go_on_top() {
+        let mut bindings = Bindings::preset(Preset::Qwerty);
+        bindings.apply("# comment\nx = 0\nscancode:45 = none # was 0\nEscape = pause\n").unwrap();
+        assert_eq!(bindings.get(45, Some("x")), Some(Action::Key(0)));
+        assert_eq!(bindings.get(45, Some("c")), None);
+        // keysyms first, then scancodes
+        assert_eq!(bindings.get(1, Some("Escape")), Some(Action::Pause));
+        assert_eq!(bindings.get(1, None), Some(Action::Quit));
+
+        let before = bindings.clone();
+        assert!(bindings.apply("x = 1\nq = jump\n").is_err());
+        assert!(bindings.apply("scancode:q = 1").is_err());
+        assert_eq!(bindings, before);
+    }
+}
@@ -0,0 +1,118 @@
+use super::*;
+
+/// Two would do if the compositor always released on time, the third
+/// keeps us drawing when it hangs on to one for a frame longer.
+pub(crate) const BUFFER_COUNT: usize = 3;
+
+#[derive(Debug)]
+struct Buffer {
+    wl_buffer: wl_buffer::WlBuffer,
+    /// Attached and not released yet, the compositor may be reading it
+    busy: bool,
+    /// Changed since this buffer was last drawn into
+    damage: Vec<render::Rect>,
+}
+
+// Past this many rects one full redraw is cheaper
+const MAX_DAMAGE: usize = 64;
+
+/// `BUFFER_COUNT` same sized wl_buffers carved out of one shm pool
+#[derive(Debug)]
+pub(crate) struct BufferPool {
+    shm: shm::ShmData,
+    buffers: Vec<Buffer>,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl BufferPool {
+    // Every buffer starts out fully damaged
+    pub fn new(conn: &Connection, wl_shm: &wl_shm::WlShm, width: i32, height: i32) -> std::io::Result<Self> {
+        let stride = width * 4;
+        let frame = (stride * height) as usize;
+        let size = frame * BUFFER_COUNT;
+        let (shm, fd) = shm::ShmData::new(size)?;
+
+        let pool = wl_shm.create_pool(conn.writer(), fd, size as i32);
+        let buffers = (0..BUFFER_COUNT)
+            .map(|i| Buffer {
+                wl_buffer: pool.create_buffer(
+                    conn.writer(),
+                    (i * frame) as i32,
+                    width,
+                    height,
+                    stride,
+                    wl_shm::Format::Xrgb8888 as u32,
+                ),
+                busy: false,
+                damage: vec![render::Rect { x: 0, y: 0, width, height }],
+            })
+            .collect();
+        // the buffers keep the pool alive on the compositor side
+        pool.destroy(conn.writer());
+
+        Ok(Self { shm, buffers, width, height })
+    }
+
+    pub fn ids(&self) -> impl Iterator<Item = u32> + '_ {
+        self.buffers.iter().map(|b| b.wl_buffer.id())
+    }
+
+    // A buffer the compositor is done with, `None` if they're all in use
+    pub fn acquire(&self) -> Option<usize> {
+        self.buffers.iter().position(|b| !b.busy)
+    }
+
+    pub fn pixels(&mut self, index: usize) -> &mut [u32] {
+        let frame = (self.width * self.height) as usize;
+        &mut self.shm.as_slice_mut::<u32>()[index * frame..][..frame]
+    }
+
+    // Marks the buffer busy until the compositor releases it
+    pub fn attach(&mut self, conn: &Connection, wl_surface: &wl_surface::WlSurface, index: usize) {
+        let buffer = &mut self.buffers[index];
+        wl_surface.attach(conn.writer(), Some(&buffer.wl_buffer), 0, 0);
+        buffer.busy = true;
+    }
+
+    // Adds `rects` to what every buffer has to redraw
+    pub fn damage(&mut self, rects: &[render::Rect]) {
+        let full = render::Rect { x: 0, y: 0, width: self.width, height: self.height };
+        for buffer in self.buffers.iter_mut() {
+            buffer.damage.extend_from_slice(rects);
+            if buffer.damage.len() > MAX_DAMAGE {
+                buffer.damage.clear();
+                buffer.damage.push(full);
+            }
+        }
+    }
+
+    pub fn damage_all(&mut self) {
+        let full = render::Rect { x: 0, y: 0, width: self.width, height: self.height };
+        self.damage(&[full]);
+    }
+
+    // What the buffer at `index` is behind on, cleared since it's about to
+    // be redrawn
+    pub fn take_damage(&mut self, index: usize) -> Vec<render::Rect> {
+        std::mem::take(&mut self.buffers[index].damage)
+    }
+
+    pub fn get(&self, id: u32) -> Option<&wl_buffer::WlBuffer> {
+        self.buffers.iter().map(|b| &b.wl_buffer).find(|b| b.id() == id)
+    }
+
+    pub fn release(&mut self, id: u32) {
+        if let Some(buffer) = self.buffers.iter_mut().find(|b| b.wl_buffer.id() == id) {
+            buffer.busy = false;
+        }
+    }
+
+    // Buffers still on screen stay there, the compositor keeps a reference
+    // of its own
+    pub fn destroy(self, conn: &Connection) {
+        for buffer in self.buffers {
+            buffer.wl_buffer.destroy(conn.writer());
+        }
+    }
+}
@@ -1,4 +1,39 @@
 
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Error {
+    UnknownOpcode { pc: usize, opcode: u16 },
+    PcOutOfBounds(usize),
+    MemoryOutOfBounds { pc: usize, addr: usize },
+    StackOverflow(usize),
+    StackUnderflow(usize),
+    RomTooLarge(usize),
+    FontOutOfBounds(usize),
+}
+
+impl std::fmt::Display for Error {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match *self {
+            Self::UnknownOpcode { pc, opcode } => {
+                write!(f, "Unknown or unimplemented instruction 0x{:04X} at 0x{:03X}", opcode, pc)
+            }
+            Self::PcOutOfBounds(pc) => write!(f, "Program counter out of memory: 0x{:X}", pc),
+            Self::MemoryOutOfBounds { pc, addr } => {
+                write!(f, "Memory access out of bounds at 0x{:X} by instruction at 0x{:03X}", addr, pc)
+            }
+            Self::StackOverflow(pc) => write!(f, "Stack overflow at 0x{:03X}", pc),
+            Self::StackUnderflow(pc) => write!(f, "Return with empty stack at 0x{:03X}", pc),
+            Self::RomTooLarge(len) => {
+                write!(f, "Rom is {} bytes, only {} fit in memory", len, MEMORY_SIZE - PROGRAM_START)
+            }
+            Self::FontOutOfBounds(base) => {
+                write!(f, "Font at 0x{:03X} doesn't fit below 0x{:03X}", base, PROGRAM_START)
+            }
+        }
+    }
+}
+
+impl std::error::Error for Error {}
+
 #[derive(Debug)]
 pub struct Chip8 {
     pub(crate) opcode: u16,
@@ -15,23 +50,87 @@ pub struct Chip8 {
     pub(crate) delay_timer: u8,
     pub(crate) keys: [bool; 16],
     pub(crate) wait_for_keys: bool,
+    pub(crate) cycles: u64,
+    pub(crate) font: [u8; FONT_SIZE],
+    pub(crate) font_base: usize,
+    decoded: Vec<Option<Decoded>>,
+    #[cfg(target_arch = "x86_64")]
+    jit: Option<Box<jit::Jit>>,
     pub need_redraw: bool,
+    /// Framebuffer areas changed since the frontend last took them
+    pub(crate) dirty: Vec<Rect>,
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Backend {
+    /// Fetch and decode every instruction
+    Interpreter,
+    /// Pre-decoded handler per address
+    #[default]
+    Cached,
+    /// Straight line code recompiled to x86-64, the rest goes through `Cached`
+    #[cfg(target_arch = "x86_64")]
+    Jit,
+}
+
+impl Backend {
+    pub const ALL: &[Backend] = &[
+        Self::Interpreter,
+        Self::Cached,
+        #[cfg(target_arch = "x86_64")]
+        Self::Jit,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Interpreter => "interpreter",
+            Self::Cached => "cached",
+            #[cfg(target_arch = "x86_64")]
+            Self::Jit => "jit",
+        }
+    }
+}
+
+type Handler = fn(&mut Chip8) -> Result<(), Error>;
+
+#[derive(Debug, Clone, Copy)]
+struct Decoded {
+    opcode: u16,
+    handler: Handler,
+}
+
+macro_rules! infallible {
+    ($f:path) => {
+        |chip8: &mut Chip8| {
+            $f(chip8);
+            Ok(())
+        }
+    };
 }
 
 impl Chip8 {
     pub fn load(&mut self, rom: &str) -> std::io::Result<()> {
-        let mut rom = std::fs::File::open(rom)?;
-        self.memory[..80].copy_from_slice(&FONTSET_1);
-        let len = std::io::Read::read(&mut rom, &mut self.memory[0x200..])?;
+        let rom = std::fs::read(rom)?;
+        self.load_bytes(&rom)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+    }
+    pub fn load_bytes(&mut self, rom: &[u8]) -> Result<(), Error> {
+        if rom.len() > MEMORY_SIZE - PROGRAM_START {
+            return Err(Error::RomTooLarge(rom.len()));
+        }
+        self.memory[self.font_base..self.font_base + FONT_SIZE].copy_from_slice(&self.font);
+        self.memory[PROGRAM_START..PROGRAM_START + rom.len()].copy_from_slice(rom);
+        self.invalidate(0..MEMORY_SIZE);
         Ok(())
     }
     pub fn new() -> Self {
         let mut memory = [0u8; MEMORY_SIZE];
-        memory[..80].copy_from_slice(&FONTSET_1);
+        let font = *Font::default().glyphs();
+        memory[..FONT_SIZE].copy_from_slice(&font);
         Self {
             memory,
             opcode: 0,
-            program_counter: 0x200,
+            program_counter: PROGRAM_START,
             index: 0,
             stack_pointer: 0,
             stack: [0; 16],
@@ -41,7 +140,14 @@ impl Chip8 {
             delay_timer: 0,
             keys: [false; 16],
             wait_for_keys: false,
-            need_redraw: false
+            cycles: 0,
+            font,
+            font_base: 0,
+            decoded: Vec::new(),
+            #[cfg(target_arch = "x86_64")]
+            jit: None,
+            need_redraw: false,
+            dirty: Vec::new(),
         }
     }
 
@@ -73,64 +179,40 @@ impl Chip8 {
     fn get_nipple(&self) -> u8 {
         (self.opcode & 0x000F) as u8
     }
-    pub fn update_opcode(&mut self) {
+    pub fn update_opcode(&mut self) -> Result<(), Error> {
+        if self.program_counter + 1 >= MEMORY_SIZE {
+            return Err(Error::PcOutOfBounds(self.program_counter));
+        }
         // 0000_0000_0000_0000
         self.opcode = (self.memory[self.program_counter] as u16) << 8
-            | (self.memory[self.program_counter + 1] as u16)
+            | (self.memory[self.program_counter + 1] as u16);
+        Ok(())
     }
-    pub fn cycle(&mut self) {
-        self.update_opcode();
+    // Opcode at PC without touching any state, used for tracing
+    pub fn peek_opcode(&self) -> u16 {
+        let hi = self.memory.get(self.program_counter).copied().unwrap_or(0);
+        let lo = self.memory.get(self.program_counter + 1).copied().unwrap_or(0);
+        (hi as u16) << 8 | (lo as u16)
+    }
+    pub fn cycle(&mut self) -> Result<(), Error> {
+        let pc = self.program_counter;
+        let handler = match self.decoded.get(pc).copied().flatten() {
+            Some(decoded) => {
+                self.opcode = decoded.opcode;
+                decoded.handler
+            }
+            None => {
+                self.update_opcode()?;
+                let handler = Self::decode(self.opcode);
+                if let Some(slot) = self.decoded.get_mut(pc) {
+                    *slot = Some(Decoded { opcode: self.opcode, handler });
+                }
+                handler
+            }
+        };
+        self.cycles += 1;
+        handler(self)?;
 
-        let identifier = (self.opcode & 0xF000) >> 12;
-        match identifier {
-            0x0 => match self.get_kk() {
-                0xE0 => self.cls(),
-                0xEE => self.ret(),
-                _ => panic!("Unknown or unimplemented insruction: 0x{:x}", self.opcode),
-            },
-            0x1 => self.jp_addr(),
-            0x2 => self.call_addr(),
-            0x3 => self.se_vxkk(),
-            0x4 => self.sne_vxkk(),
-            0x5 => self.se_vxvy(),
-            0x6 => self.ld_vxkk(),
-            0x7 => self.add_vxkk(),
-            0x8 => match self.get_nipple() {
-                0x0 => self.ld_vxvy(),
-                0x1 => self.or_vxvy(),
-                0x2 => self.and_vxvy(),
-                0x3 => self.xor_vxvy(),
-                0x4 => self.add_vxvy(),
-                0x5 => self.sub_vxvy(),
-                0x6 => self.shr_vx(),
-                0x7 => self.subn_vxvy(),
-                0xE => self.shl_vx(),
-                _ => panic!("Unknown or unimplemented insruction: 0x{:x}", self.opcode),
-            },
-            0x9 => self.sne_vxvy(),
-            0xA => self.ld_iaddr(),
-            0xB => self.jp_v0addr(),
-            0xC => self.rnd_xkk(),
-            0xD => self.drw_xyn(),
-            0xE => match self.get_kk() {
-                0x9E => self.skp_vx(),
-                0xA1 => self.sknp_vx(),
-                _ => panic!("Unknown or unimplemented insruction: 0x{:x}", self.opcode),
-            },
-            0xF => match self.get_kk() {
-                0x07 => self.ld_vxdt(),
-                0x0A => self.ld_vxk(),
-                0x15 => self.ld_dtvx(),
-                0x18 => self.ld_stvx(),
-                0x1E => self.add_ivx(),
-                0x29 => self.ld_fvx(),
-                0x33 => self.ld_bvx(),
-                0x55 => self.ld_ivx(),
-                0x65 => self.ld_vxi(),
-                _ => panic!("Unknown or unimplemented insruction: 0x{:0x}", self.opcode),
-            },
-            _ => panic!("Unknown or unimplemented insruction: 0x{:0x}", self.opcode),
-        }
         if !self.wait_for_keys {
             if self.delay_timer > 0 {
                 self.delay_timer -= 1
@@ -139,10 +221,181 @@ impl Chip8 {
                 self.sound_timer -= 1
             }
         }
+        Ok(())
+    }
+    fn decode(opcode: u16) -> Handler {
+        let identifier = (opcode & 0xF000) >> 12;
+        match identifier {
+            0x0 => match opcode & 0x00FF {
+                0xE0 => infallible!(Self::cls),
+                0xEE => Self::ret,
+                _ => Self::unknown_op,
+            },
+            0x1 => infallible!(Self::jp_addr),
+            0x2 => Self::call_addr,
+            0x3 => infallible!(Self::se_vxkk),
+            0x4 => infallible!(Self::sne_vxkk),
+            0x5 => infallible!(Self::se_vxvy),
+            0x6 => infallible!(Self::ld_vxkk),
+            0x7 => infallible!(Self::add_vxkk),
+            0x8 => match opcode & 0x000F {
+                0x0 => infallible!(Self::ld_vxvy),
+                0x1 => infallible!(Self::or_vxvy),
+                0x2 => infallible!(Self::and_vxvy),
+                0x3 => infallible!(Self::xor_vxvy),
+                0x4 => infallible!(Self::add_vxvy),
+                0x5 => infallible!(Self::sub_vxvy),
+                0x6 => infallible!(Self::shr_vx),
+                0x7 => infallible!(Self::subn_vxvy),
+                0xE => infallible!(Self::shl_vx),
+                _ => Self::unknown_op,
+            },
+            0x9 => infallible!(Self::sne_vxvy),
+            0xA => infallible!(Self::ld_iaddr),
+            0xB => infallible!(Self::jp_v0addr),
+            0xC => infallible!(Self::rnd_xkk),
+            0xD => Self::drw_xyn,
+            0xE => match opcode & 0x00FF {
+                0x9E => infallible!(Self::skp_vx),
+                0xA1 => infallible!(Self::sknp_vx),
+                _ => Self::unknown_op,
+            },
+            0xF => match opcode & 0x00FF {
+                0x07 => infallible!(Self::ld_vxdt),
+                0x0A => infallible!(Self::ld_vxk),
+                0x15 => infallible!(Self::ld_dtvx),
+                0x18 => infallible!(Self::ld_stvx),
+                0x1E => infallible!(Self::add_ivx),
+                0x29 => infallible!(Self::ld_fvx),
+                0x33 => Self::ld_bvx,
+                0x55 => Self::ld_ivx,
+                0x65 => Self::ld_vxi,
+                _ => Self::unknown_op,
+            },
+            _ => Self::unknown_op,
+        }
+    }
+    // The cache keeps a pre-decoded handler per address so `cycle` skips
+    // fetching and dispatching for code it already ran. `new` starts out as
+    // a plain interpreter.
+    pub fn set_backend(&mut self, backend: Backend) -> std::io::Result<()> {
+        let cached = backend != Backend::Interpreter;
+        self.decoded = if cached { vec![None; MEMORY_SIZE] } else { Vec::new() };
+        #[cfg(target_arch = "x86_64")]
+        {
+            self.jit = match backend {
+                Backend::Jit => Some(Box::new(jit::Jit::new()?)),
+                _ => None,
+            };
+        }
+        Ok(())
+    }
+    // Runs `budget` instructions, compiled blocks first when there's one
+    // at PC that fits. Returns how many ran, which is only ever short of
+    // `budget` on error.
+    pub fn run(&mut self, budget: u64) -> Result<u64, Error> {
+        let mut ran = 0;
+        while ran < budget {
+            #[cfg(target_arch = "x86_64")]
+            if let Some(n) = self.run_block(budget - ran) {
+                ran += n;
+                continue;
+            }
+            self.cycle()?;
+            ran += 1;
+        }
+        Ok(ran)
+    }
+    #[cfg(target_arch = "x86_64")]
+    fn run_block(&mut self, budget: u64) -> Option<u64> {
+        if self.wait_for_keys {
+            return None;
+        }
+        let block = self.jit.as_mut()?.block(&self.memory, self.program_counter)?;
+        if block.len > budget {
+            return None;
+        }
+        // Blocks never contain Fx07/Fx15/Fx18 or Fx0A, so ticking the
+        // timers afterwards is the same as once per instruction
+        unsafe { (block.code)(self as *mut Self) };
+        let ticks = block.len.min(u8::MAX as u64) as u8;
+        self.delay_timer = self.delay_timer.saturating_sub(ticks);
+        self.sound_timer = self.sound_timer.saturating_sub(ticks);
+        self.cycles += block.len;
+        Some(block.len)
+    }
+    // Drops cached handlers overlapping written memory, the instruction
+    // starting one byte before `range` covers its first byte too.
+    fn invalidate(&mut self, range: std::ops::Range<usize>) {
+        #[cfg(target_arch = "x86_64")]
+        if let Some(jit) = self.jit.as_mut() {
+            jit.invalidate(range.clone());
+        }
+        let start = range.start.saturating_sub(1);
+        let end = range.end.min(self.decoded.len());
+        if start < end {
+            self.decoded[start..end].fill(None);
+        }
+    }
+    // Moves the font to `base`, it has to fit below the program
+    pub fn set_font(&mut self, font: &[u8; FONT_SIZE], base: usize) -> Result<(), Error> {
+        if base + FONT_SIZE > PROGRAM_START {
+            return Err(Error::FontOutOfBounds(base));
+        }
+        let old = self.font_base;
+        self.memory[old..old + FONT_SIZE].fill(0);
+        self.invalidate(old..old + FONT_SIZE);
+        self.font = *font;
+        self.font_base = base;
+        self.poke(base, font);
+        Ok(())
+    }
+    // Every memory write outside of instructions should go through here
+    pub(crate) fn poke(&mut self, addr: usize, bytes: &[u8]) {
+        self.memory[addr..addr + bytes.len()].copy_from_slice(bytes);
+        self.invalidate(addr..addr + bytes.len());
     }
     fn inc_pc(&mut self) {
         self.program_counter += 2;
     }
+    fn unknown(&self) -> Error {
+        Error::UnknownOpcode { pc: self.program_counter, opcode: self.opcode }
+    }
+    fn unknown_op(&mut self) -> Result<(), Error> {
+        Err(self.unknown())
+    }
+    // `len` bytes starting at I, as long as they're inside memory
+    fn index_range(&self, len: usize) -> Result<std::ops::Range<usize>, Error> {
+        let end = self.index + len;
+        if end > MEMORY_SIZE {
+            return Err(Error::MemoryOutOfBounds { pc: self.program_counter, addr: end - 1 });
+        }
+        Ok(self.index..end)
+    }
+    // Sprites wrap around the edges, so one draw can touch up to four
+    // separate areas
+    fn mark_dirty(&mut self, x: usize, y: usize, width: usize, height: usize) {
+        if self.dirty.first() == Some(&FULL_SCREEN) {
+            return;
+        }
+        for (x, width) in wrapped(x, width, WIDTH) {
+            for (y, height) in wrapped(y, height, HEIGHT) {
+                self.dirty.push(Rect { x: x as i32, y: y as i32, width: width as i32, height: height as i32 });
+            }
+        }
+        // not worth tracking piece by piece anymore
+        if self.dirty.len() >= MAX_DIRTY {
+            self.dirty.clear();
+            self.dirty.push(FULL_SCREEN);
+        }
+    }
+}
+
+// `start..start + len` split where it wraps past `size`
+fn wrapped(start: usize, len: usize, size: usize) -> impl Iterator<Item = (usize, usize)> {
+    let start = start % size;
+    let first = len.min(size - start);
+    [(start, first), (0, len - first)].into_iter().filter(|(_, len)| *len > 0)
 }
 
 // Instructions
@@ -150,12 +403,19 @@ impl Chip8 {
     // 0x00E0
     fn cls(&mut self) {
         self.framebuffer = [0; WIDTH * HEIGHT];
+        self.dirty.clear();
+        self.dirty.push(FULL_SCREEN);
+        self.need_redraw = true;
         self.inc_pc();
     }
     // 0x00EE
-    fn ret(&mut self) {
+    fn ret(&mut self) -> Result<(), Error> {
+        if self.stack_pointer == 0 {
+            return Err(Error::StackUnderflow(self.program_counter));
+        }
         self.stack_pointer -= 1;
         self.program_counter = self.stack[self.stack_pointer] + 2;
+        Ok(())
     }
     // 0x1nnn
     fn jp_addr(&mut self) {
@@ -163,11 +423,15 @@ impl Chip8 {
         self.program_counter = addr;
     }
     // 0x2nnn
-    fn call_addr(&mut self) {
+    fn call_addr(&mut self) -> Result<(), Error> {
+        if self.stack_pointer == self.stack.len() {
+            return Err(Error::StackOverflow(self.program_counter));
+        }
         let addr = self.get_addr();
         self.stack[self.stack_pointer] = self.program_counter;
         self.stack_pointer += 1;
         self.program_counter = addr;
+        Ok(())
     }
     // 0x3xkk
     fn se_vxkk(&mut self) {
@@ -249,13 +513,13 @@ impl Chip8 {
         self.registers[0xF] = if result > 255 { 1 } else { 0 };
         self.inc_pc();
     }
-    // 0x8xy5
+    // 0x8xy5, VF is NOT borrow, so equal operands set it
     fn sub_vxvy(&mut self) {
         let x = self.get_x();
         let y = self.get_y();
         let vx = self.registers[x];
         let vy = self.registers[y];
-        self.registers[0xF] = if vx > vy { 1 } else { 0 };
+        self.registers[0xF] = if vx >= vy { 1 } else { 0 };
         self.registers[x] = vx.wrapping_sub(vy);
         self.inc_pc();
     }
@@ -267,13 +531,13 @@ impl Chip8 {
         self.registers[x] >>= 1;
         self.inc_pc();
     }
-    // 0x8xy7
+    // 0x8xy7, VF is NOT borrow like SUB
     fn subn_vxvy(&mut self) {
         let x = self.get_x();
         let y = self.get_y();
         let vx = self.registers[x];
         let vy = self.registers[y];
-        self.registers[0xF] = if vy > vx { 1 } else { 0 };
+        self.registers[0xF] = if vy >= vx { 1 } else { 0 };
         self.registers[x] = vy.wrapping_sub(vx);
         self.inc_pc();
     }
@@ -319,32 +583,38 @@ impl Chip8 {
     // 0xDxyn
     // shamelessly copied from https://github.com/starrhorne/chip8-rust
     // Can't be bothred
-    fn drw_xyn(&mut self) {
+    fn drw_xyn(&mut self) -> Result<(), Error> {
         let n = self.get_nipple() as usize;
         let x = self.get_x();
         let y = self.get_y();
+        let sprite = self.index_range(n)?;
+        self.mark_dirty(self.registers[x] as usize, self.registers[y] as usize, 8, n);
+        // set by a collision in this sprite only, not left over from before
+        self.registers[0xF] = 0;
         for byte in 0..n {
             let y = (self.registers[y] as usize + byte) % HEIGHT;
             for bit in 0..8 {
                 let x = (self.registers[x] as usize + bit) % WIDTH;
-                let color = (self.memory[self.index + byte] >> (7 - bit)) & 1;
+                let color = (self.memory[sprite.start + byte] >> (7 - bit)) & 1;
                 self.registers[0xF] |= color & self.framebuffer[y * WIDTH + x];
                 self.framebuffer[y * WIDTH + x] ^= color;
             }
         }
         self.need_redraw = true;
         self.inc_pc();
+        Ok(())
     }
     // Ex9E
+    // Only the low nipple of Vx names a key
     fn skp_vx(&mut self) {
-        if self.keys[self.registers[self.get_x()] as usize] {
+        if self.keys[(self.registers[self.get_x()] & 0xF) as usize] {
             self.inc_pc();
         }
         self.inc_pc();
     }
     // ExA1
     fn sknp_vx(&mut self) {
-        if !self.keys[self.registers[self.get_x()] as usize] {
+        if !self.keys[(self.registers[self.get_x()] & 0xF) as usize] {
             self.inc_pc();
         }
         self.inc_pc();
@@ -388,54 +658,55 @@ impl Chip8 {
     }
     // 0xFx29
     fn ld_fvx(&mut self) {
-        self.index = (self.registers[self.get_x()] as usize) * 5;
+        let digit = (self.registers[self.get_x()] & 0xF) as usize;
+        self.index = self.font_base + digit * GLYPH_SIZE;
         self.inc_pc();
     }
     // 0xFx33
-    fn ld_bvx(&mut self) {
+    fn ld_bvx(&mut self) -> Result<(), Error> {
         let vx = self.registers[self.get_x()];
-        self.memory[self.index] = vx / 100;
-        self.memory[self.index + 1] = (vx / 10) % 10;
-        self.memory[self.index + 2] = vx % 10;
+        let range = self.index_range(3)?;
+        self.invalidate(range.clone());
+        let bcd = &mut self.memory[range];
+        bcd[0] = vx / 100;
+        bcd[1] = (vx / 10) % 10;
+        bcd[2] = vx % 10;
         self.inc_pc();
+        Ok(())
     }
     // 0xFx55
-    fn ld_ivx(&mut self) {
-        for i in 0..=self.get_x() {
-            self.memory[self.index + i] = self.registers[i];
-        }
+    fn ld_ivx(&mut self) -> Result<(), Error> {
+        let x = self.get_x();
+        let range = self.index_range(x + 1)?;
+        self.invalidate(range.clone());
+        self.memory[range].copy_from_slice(&self.registers[..=x]);
         self.inc_pc();
+        Ok(())
     }
     // 0xFx65
-    fn ld_vxi(&mut self) {
-        for i in 0..=self.get_x() {
-            self.registers[i] = self.memory[self.index + i];
-        }
+    fn ld_vxi(&mut self) -> Result<(), Error> {
+        let x = self.get_x();
+        let range = self.index_range(x + 1)?;
+        self.registers[..=x].copy_from_slice(&self.memory[range]);
         self.inc_pc();
+        Ok(())
     }
 }
-const FONTSET_1: [u8; 80] = [
-    0xF0, 0x90, 0x90, 0x90, 0xF0, // 0
-    0x20, 0x60, 0x20, 0x20, 0x70, // 1
-    0xF0, 0x10, 0xF0, 0x80, 0xF0, // 2
-    0xF0, 0x10, 0xF0, 0x10, 0xF0, // 3
-    0x90, 0x90, 0xF0, 0x10, 0x10, // 4
-    0xF0, 0x80, 0xF0, 0x10, 0xF0, // 5
-    0xF0, 0x80, 0xF0, 0x90, 0xF0, // 6
-    0xF0, 0x10, 0x20, 0x40, 0x40, // 7
-    0xF0, 0x90, 0xF0, 0x90, 0xF0, // 8
-    0xF0, 0x90, 0xF0, 0x10, 0xF0, // 9
-    0xF0, 0x90, 0xF0, 0x90, 0x90, // A
-    0xE0, 0x90, 0xE0, 0x90, 0xE0, // B
-    0xF0, 0x80, 0x80, 0x80, 0xF0, // C
-    0xE0, 0x90, 0x90, 0x90, 0xE0, // D
-    0xF0, 0x80, 0xF0, 0x80, 0xF0, // E
-    0xF0, 0x80, 0xF0, 0x80, 0x80, // F
-];
 
 pub const WIDTH: usize = 64;
 pub const HEIGHT: usize = 32;
 pub const SCALE: usize = 10;
 pub const MEMORY_SIZE: usize = 4096;
+pub const PROGRAM_START: usize = 0x200;
+const MAX_DIRTY: usize = 32;
+const FULL_SCREEN: Rect = Rect { x: 0, y: 0, width: WIDTH as i32, height: HEIGHT as i32 };
+
+use super::font::{Font, FONT_SIZE, GLYPH_SIZE};
+use super::render::Rect;
+#[cfg(target_arch = "x86_64")]
+use super::jit;
+
+#[cfg(test)]
+mod tests;
 
 use super::*;
@@ -0,0 +1,519 @@
+use super::*;
+
+// Writes `opcode` at PC and runs a single instruction
+fn exec(chip8: &mut Chip8, opcode: u16) -> Result<(), Error> {
+    chip8.poke(chip8.program_counter, &opcode.to_be_bytes());
+    chip8.run(1).map(|ran| assert_eq!(ran, 1))
+}
+
+fn with_backend(backend: Backend) -> Chip8 {
+    let mut chip8 = Chip8::new();
+    chip8.set_backend(backend).unwrap();
+    chip8
+}
+
+struct Case {
+    name: &'static str,
+    opcode: u16,
+    setup: fn(&mut Chip8),
+    check: fn(&Chip8),
+}
+
+const NEXT: usize = PROGRAM_START + 2;
+const SKIP: usize = PROGRAM_START + 4;
+
+#[rustfmt::skip]
+const CASES: &[Case] = &[
+    Case { name: "CLS", opcode: 0x00E0,
+        setup: |c| c.framebuffer[100] = 1,
+        check: |c| { assert!(c.framebuffer.iter().all(|p| *p == 0)); assert_eq!(c.program_counter, NEXT) } },
+    Case { name: "RET", opcode: 0x00EE,
+        setup: |c| { c.stack[0] = 0x300; c.stack_pointer = 1 },
+        check: |c| { assert_eq!(c.program_counter, 0x302); assert_eq!(c.stack_pointer, 0) } },
+    Case { name: "JP addr", opcode: 0x1ABC,
+        setup: |_| {},
+        check: |c| assert_eq!(c.program_counter, 0xABC) },
+    Case { name: "CALL addr", opcode: 0x2ABC,
+        setup: |_| {},
+        check: |c| { assert_eq!(c.program_counter, 0xABC); assert_eq!(c.stack[0], PROGRAM_START); assert_eq!(c.stack_pointer, 1) } },
+    Case { name: "SE Vx, kk taken", opcode: 0x3142,
+        setup: |c| c.registers[1] = 0x42,
+        check: |c| assert_eq!(c.program_counter, SKIP) },
+    Case { name: "SE Vx, kk not taken", opcode: 0x3142,
+        setup: |c| c.registers[1] = 0x41,
+        check: |c| assert_eq!(c.program_counter, NEXT) },
+    Case { name: "SNE Vx, kk taken", opcode: 0x4142,
+        setup: |c| c.registers[1] = 0x41,
+        check: |c| assert_eq!(c.program_counter, SKIP) },
+    Case { name: "SNE Vx, kk not taken", opcode: 0x4142,
+        setup: |c| c.registers[1] = 0x42,
+        check: |c| assert_eq!(c.program_counter, NEXT) },
+    Case { name: "SE Vx, Vy taken", opcode: 0x5120,
+        setup: |c| { c.registers[1] = 7; c.registers[2] = 7 },
+        check: |c| assert_eq!(c.program_counter, SKIP) },
+    Case { name: "SE Vx, Vy not taken", opcode: 0x5120,
+        setup: |c| { c.registers[1] = 7; c.registers[2] = 8 },
+        check: |c| assert_eq!(c.program_counter, NEXT) },
+    Case { name: "LD Vx, kk", opcode: 0x6A55,
+        setup: |_| {},
+        check: |c| { assert_eq!(c.registers[0xA], 0x55); assert_eq!(c.program_counter, NEXT) } },
+    Case { name: "ADD Vx, kk wraps without touching VF", opcode: 0x7102,
+        setup: |c| { c.registers[1] = 0xFF; c.registers[0xF] = 7 },
+        check: |c| { assert_eq!(c.registers[1], 0x01); assert_eq!(c.registers[0xF], 7) } },
+    Case { name: "LD Vx, Vy", opcode: 0x8120,
+        setup: |c| c.registers[2] = 9,
+        check: |c| { assert_eq!(c.registers[1], 9); assert_eq!(c.program_counter, NEXT) } },
+    Case { name: "OR Vx, Vy", opcode: 0x8121,
+        setup: |c| { c.registers[1] = 0b1010; c.registers[2] = 0b0101 },
+        check: |c| assert_eq!(c.registers[1], 0b1111) },
+    Case { name: "AND Vx, Vy", opcode: 0x8122,
+        setup: |c| { c.registers[1] = 0b1110; c.registers[2] = 0b0111 },
+        check: |c| assert_eq!(c.registers[1], 0b0110) },
+    Case { name: "XOR Vx, Vy", opcode: 0x8123,
+        setup: |c| { c.registers[1] = 0b1110; c.registers[2] = 0b0111 },
+        check: |c| assert_eq!(c.registers[1], 0b1001) },
+    Case { name: "ADD Vx, Vy no carry", opcode: 0x8124,
+        setup: |c| { c.registers[1] = 0x10; c.registers[2] = 0x20; c.registers[0xF] = 1 },
+        check: |c| { assert_eq!(c.registers[1], 0x30); assert_eq!(c.registers[0xF], 0) } },
+    Case { name: "ADD Vx, Vy carry", opcode: 0x8124,
+        setup: |c| { c.registers[1] = 0xFF; c.registers[2] = 0x02 },
+        check: |c| { assert_eq!(c.registers[1], 0x01); assert_eq!(c.registers[0xF], 1) } },
+    Case { name: "ADD VF, Vy flag wins", opcode: 0x8F14,
+        setup: |c| { c.registers[0xF] = 0xFF; c.registers[1] = 0x02 },
+        check: |c| assert_eq!(c.registers[0xF], 1) },
+    Case { name: "SUB Vx, Vy no borrow", opcode: 0x8125,
+        setup: |c| { c.registers[1] = 5; c.registers[2] = 3 },
+        check: |c| { assert_eq!(c.registers[1], 2); assert_eq!(c.registers[0xF], 1) } },
+    Case { name: "SUB Vx, Vy equal", opcode: 0x8125,
+        setup: |c| { c.registers[1] = 5; c.registers[2] = 5 },
+        check: |c| { assert_eq!(c.registers[1], 0); assert_eq!(c.registers[0xF], 1) } },
+    Case { name: "SUB Vx, Vy borrow", opcode: 0x8125,
+        setup: |c| { c.registers[1] = 3; c.registers[2] = 5 },
+        check: |c| { assert_eq!(c.registers[1], 0xFE); assert_eq!(c.registers[0xF], 0) } },
+    Case { name: "SHR Vx", opcode: 0x8106,
+        setup: |c| c.registers[1] = 0b0000_0011,
+        check: |c| { assert_eq!(c.registers[1], 0b0000_0001); assert_eq!(c.registers[0xF], 1) } },
+    Case { name: "SUBN Vx, Vy no borrow", opcode: 0x8127,
+        setup: |c| { c.registers[1] = 3; c.registers[2] = 5 },
+        check: |c| { assert_eq!(c.registers[1], 2); assert_eq!(c.registers[0xF], 1) } },
+    Case { name: "SUBN Vx, Vy equal", opcode: 0x8127,
+        setup: |c| { c.registers[1] = 5; c.registers[2] = 5 },
+        check: |c| { assert_eq!(c.registers[1], 0); assert_eq!(c.registers[0xF], 1) } },
+    Case { name: "SUBN Vx, Vy borrow", opcode: 0x8127,
+        setup: |c| { c.registers[1] = 5; c.registers[2] = 3 },
+        check: |c| { assert_eq!(c.registers[1], 0xFE); assert_eq!(c.registers[0xF], 0) } },
+    Case { name: "SHL Vx", opcode: 0x810E,
+        setup: |c| c.registers[1] = 0b1000_0001,
+        check: |c| { assert_eq!(c.registers[1], 0b0000_0010); assert_eq!(c.registers[0xF], 1) } },
+    Case { name: "SNE Vx, Vy taken", opcode: 0x9120,
+        setup: |c| { c.registers[1] = 1; c.registers[2] = 2 },
+        check: |c| assert_eq!(c.program_counter, SKIP) },
+    Case { name: "SNE Vx, Vy not taken", opcode: 0x9120,
+        setup: |c| { c.registers[1] = 2; c.registers[2] = 2 },
+        check: |c| assert_eq!(c.program_counter, NEXT) },
+    Case { name: "LD I, addr", opcode: 0xA123,
+        setup: |_| {},
+        check: |c| { assert_eq!(c.index, 0x123); assert_eq!(c.program_counter, NEXT) } },
+    Case { name: "JP V0, addr", opcode: 0xB300,
+        setup: |c| c.registers[0] = 0x10,
+        check: |c| assert_eq!(c.program_counter, 0x310) },
+    Case { name: "RND Vx, kk masks", opcode: 0xC100,
+        setup: |c| c.registers[1] = 0xFF,
+        check: |c| { assert_eq!(c.registers[1], 0); assert_eq!(c.program_counter, NEXT) } },
+    Case { name: "DRW draws", opcode: 0xD121,
+        setup: |c| { c.index = 0x300; c.memory[0x300] = 0b1000_0001; c.registers[1] = 4; c.registers[2] = 2; c.registers[0xF] = 1 },
+        check: |c| {
+            assert_eq!(c.framebuffer[2 * WIDTH + 4], 1);
+            assert_eq!(c.framebuffer[2 * WIDTH + 11], 1);
+            assert_eq!(c.registers[0xF], 0);
+            assert!(c.need_redraw);
+        } },
+    Case { name: "DRW collides", opcode: 0xD121,
+        setup: |c| { c.index = 0x300; c.memory[0x300] = 0b1000_0000; c.framebuffer[0] = 1 },
+        check: |c| { assert_eq!(c.framebuffer[0], 0); assert_eq!(c.registers[0xF], 1) } },
+    Case { name: "DRW wraps", opcode: 0xD121,
+        setup: |c| { c.index = 0x300; c.memory[0x300] = 0b1100_0000; c.registers[1] = 63; c.registers[2] = 31 },
+        check: |c| { assert_eq!(c.framebuffer[31 * WIDTH + 63], 1); assert_eq!(c.framebuffer[31 * WIDTH], 1) } },
+    Case { name: "SKP Vx taken", opcode: 0xE19E,
+        setup: |c| { c.registers[1] = 0xA; c.keys[0xA] = true },
+        check: |c| assert_eq!(c.program_counter, SKIP) },
+    Case { name: "SKP Vx ignores high nipple", opcode: 0xE19E,
+        setup: |c| { c.registers[1] = 0xFA; c.keys[0xA] = true },
+        check: |c| assert_eq!(c.program_counter, SKIP) },
+    Case { name: "SKNP Vx taken", opcode: 0xE1A1,
+        setup: |c| c.registers[1] = 0xA,
+        check: |c| assert_eq!(c.program_counter, SKIP) },
+    Case { name: "SKNP Vx not taken", opcode: 0xE1A1,
+        setup: |c| { c.registers[1] = 0xA; c.keys[0xA] = true },
+        check: |c| assert_eq!(c.program_counter, NEXT) },
+    Case { name: "LD Vx, DT", opcode: 0xF107,
+        setup: |c| c.delay_timer = 9,
+        check: |c| { assert_eq!(c.registers[1], 9); assert_eq!(c.delay_timer, 8) } },
+    Case { name: "LD Vx, K waits", opcode: 0xF10A,
+        setup: |c| c.delay_timer = 9,
+        check: |c| { assert_eq!(c.program_counter, PROGRAM_START); assert!(c.wait_for_keys); assert_eq!(c.delay_timer, 9) } },
+    Case { name: "LD Vx, K pressed", opcode: 0xF10A,
+        setup: |c| c.keys[0xC] = true,
+        check: |c| { assert_eq!(c.program_counter, NEXT); assert_eq!(c.registers[1], 0xC); assert!(!c.wait_for_keys) } },
+    Case { name: "LD DT, Vx", opcode: 0xF115,
+        setup: |c| c.registers[1] = 5,
+        check: |c| assert_eq!(c.delay_timer, 4) },
+    Case { name: "LD ST, Vx", opcode: 0xF118,
+        setup: |c| c.registers[1] = 5,
+        check: |c| assert_eq!(c.sound_timer, 4) },
+    Case { name: "ADD I, Vx", opcode: 0xF11E,
+        setup: |c| { c.index = 0x100; c.registers[1] = 0x10 },
+        check: |c| { assert_eq!(c.index, 0x110); assert_eq!(c.program_counter, NEXT) } },
+    Case { name: "LD F, Vx", opcode: 0xF129,
+        setup: |c| c.registers[1] = 0xA,
+        check: |c| { assert_eq!(c.index, 0xA * 5); assert_eq!(c.memory[c.index], 0xF0) } },
+    Case { name: "LD F, Vx only uses the low nipple", opcode: 0xF129,
+        setup: |c| c.registers[1] = 0x3A,
+        check: |c| assert_eq!(c.index, 0xA * 5) },
+    Case { name: "LD B, Vx", opcode: 0xF133,
+        setup: |c| { c.index = 0x300; c.registers[1] = 254 },
+        check: |c| assert_eq!(c.memory[0x300..0x303], [2, 5, 4]) },
+    Case { name: "LD [I], Vx", opcode: 0xF255,
+        setup: |c| { c.index = 0x300; c.registers[..4].copy_from_slice(&[1, 2, 3, 4]) },
+        check: |c| { assert_eq!(c.memory[0x300..0x304], [1, 2, 3, 0]); assert_eq!(c.index, 0x300) } },
+    Case { name: "LD Vx, [I]", opcode: 0xF265,
+        setup: |c| { c.index = 0x300; c.memory[0x300..0x304].copy_from_slice(&[1, 2, 3, 4]) },
+        check: |c| assert_eq!(c.registers[..4], [1, 2, 3, 0]) },
+];
+
+#[test]
+fn instructions() {
+    for backend in Backend::ALL {
+        for case in CASES {
+            let mut chip8 = with_backend(*backend);
+            (case.setup)(&mut chip8);
+            if let Err(e) = exec(&mut chip8, case.opcode) {
+                panic!("{} ({}): {}", case.name, backend.name(), e);
+            }
+            (case.check)(&chip8);
+        }
+    }
+}
+
+type ErrorCase = (&'static str, u16, fn(&mut Chip8));
+
+#[rustfmt::skip]
+const ERROR_CASES: &[ErrorCase] = &[
+    ("LD B, Vx at end of memory", 0xF133, |c| c.index = 0xFFE),
+    ("LD [I], Vx at end of memory", 0xF255, |c| c.index = 0xFFE),
+    ("LD Vx, [I] at end of memory", 0xF265, |c| c.index = 0xFFF),
+    ("DRW at end of memory", 0xD002, |c| c.index = 0xFFF),
+    ("DRW after ADD I overflow", 0xD001, |c| c.index = 0x10FE),
+];
+
+#[test]
+fn out_of_bounds_memory() {
+    for (name, opcode, setup) in ERROR_CASES {
+        let mut chip8 = Chip8::new();
+        setup(&mut chip8);
+        match exec(&mut chip8, *opcode) {
+            Err(Error::MemoryOutOfBounds { pc, .. }) => assert_eq!(pc, PROGRAM_START, "{}", name),
+            res => panic!("{}: expected out of bounds, got {:?}", name, res),
+        }
+    }
+}
+
+#[test]
+fn last_byte_of_memory_is_reachable() {
+    let mut chip8 = Chip8::new();
+    chip8.index = 0xFFF;
+    chip8.registers[0] = 0xAB;
+    exec(&mut chip8, 0xF055).unwrap();
+    assert_eq!(chip8.memory[0xFFF], 0xAB);
+}
+
+#[test]
+fn stack_errors() {
+    let mut chip8 = Chip8::new();
+    assert_eq!(exec(&mut chip8, 0x00EE), Err(Error::StackUnderflow(PROGRAM_START)));
+
+    let mut chip8 = Chip8::new();
+    for _ in 0..16 {
+        exec(&mut chip8, 0x2200).unwrap();
+    }
+    assert_eq!(exec(&mut chip8, 0x2200), Err(Error::StackOverflow(PROGRAM_START)));
+}
+
+#[test]
+fn pc_and_opcode_errors() {
+    let mut chip8 = Chip8::new();
+    chip8.program_counter = 0xFFF;
+    assert_eq!(chip8.cycle(), Err(Error::PcOutOfBounds(0xFFF)));
+
+    for opcode in [0x0123, 0x8008, 0xE000, 0xF0FF] {
+        let mut chip8 = Chip8::new();
+        assert_eq!(exec(&mut chip8, opcode), Err(Error::UnknownOpcode { pc: PROGRAM_START, opcode }));
+    }
+}
+
+#[test]
+fn load_bytes() {
+    let mut chip8 = Chip8::new();
+    chip8.load_bytes(&[0x12, 0x34]).unwrap();
+    assert_eq!(chip8.memory[PROGRAM_START..PROGRAM_START + 2], [0x12, 0x34]);
+    assert_eq!(chip8.memory[..FONT_SIZE], *Font::Schip.glyphs());
+
+    let max = MEMORY_SIZE - PROGRAM_START;
+    assert!(chip8.load_bytes(&vec![0xFF; max]).is_ok());
+    assert_eq!(chip8.load_bytes(&vec![0; max + 1]), Err(Error::RomTooLarge(max + 1)));
+}
+
+#[test]
+fn fonts() {
+    for font in Font::ALL {
+        let mut chip8 = Chip8::new();
+        chip8.set_font(font.glyphs(), 0x50).unwrap();
+        chip8.load_bytes(&[]).unwrap();
+        assert!(chip8.memory[..0x50].iter().all(|b| *b == 0), "{}", font.name());
+        assert_eq!(chip8.memory[0x50..0x50 + FONT_SIZE], *font.glyphs(), "{}", font.name());
+
+        chip8.registers[1] = 0xF;
+        exec(&mut chip8, 0xF129).unwrap();
+        assert_eq!(chip8.index, 0x50 + 0xF * GLYPH_SIZE);
+        assert_eq!(chip8.memory[chip8.index..chip8.index + GLYPH_SIZE], font.glyphs()[75..]);
+    }
+
+    let mut chip8 = Chip8::new();
+    let max = PROGRAM_START - FONT_SIZE;
+    assert!(chip8.set_font(&[0xAA; FONT_SIZE], max).is_ok());
+    assert_eq!(chip8.set_font(&[0; FONT_SIZE], max + 1), Err(Error::FontOutOfBounds(max + 1)));
+}
+
+#[test]
+fn dirty_regions() {
+    let rect = |x, y, width, height| Rect { x, y, width, height };
+    let mut chip8 = Chip8::new();
+    chip8.index = 0x300;
+    chip8.registers[0] = 10;
+    chip8.registers[1] = 4;
+    exec(&mut chip8, 0xD013).unwrap();
+    assert_eq!(chip8.dirty, [rect(10, 4, 8, 3)]);
+
+    // wraps on both axes
+    chip8.dirty.clear();
+    chip8.registers[0] = 60;
+    chip8.registers[1] = 30;
+    exec(&mut chip8, 0xD014).unwrap();
+    assert_eq!(chip8.dirty, [rect(60, 30, 4, 2), rect(60, 0, 4, 2), rect(0, 30, 4, 2), rect(0, 0, 4, 2)]);
+
+    exec(&mut chip8, 0x00E0).unwrap();
+    assert_eq!(chip8.dirty, [rect(0, 0, WIDTH as i32, HEIGHT as i32)]);
+
+    chip8.dirty.clear();
+    for _ in 0..100 {
+        exec(&mut chip8, 0xD011).unwrap();
+    }
+    assert_eq!(chip8.dirty, [rect(0, 0, WIDTH as i32, HEIGHT as i32)]);
+}
+
+// xorshift64*, good enough to throw garbage at the interpreter
+// without pulling a crate in.
+pub(crate) struct Rng(u64);
+
+impl Rng {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(seed.max(1))
+    }
+    pub(crate) fn next(&mut self) -> u64 {
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+    pub(crate) fn below(&mut self, n: usize) -> usize {
+        (self.next() % n as u64) as usize
+    }
+    pub(crate) fn fill(&mut self, buf: &mut [u8]) {
+        for b in buf {
+            *b = self.next() as u8;
+        }
+    }
+}
+
+// Random machine state biased towards the edges of memory and the stack
+fn random_state(rng: &mut Rng) -> Chip8 {
+    const INDEXES: [usize; 6] = [0, 0x200, 0xFF0, 0xFFD, 0xFFE, 0xFFF];
+    let mut chip8 = Chip8::new();
+    rng.fill(&mut chip8.registers);
+    chip8.index = match rng.below(3) {
+        0 => INDEXES[rng.below(INDEXES.len())],
+        _ => rng.below(MEMORY_SIZE),
+    };
+    chip8.stack_pointer = [0, 15, 16][rng.below(3)];
+    chip8.delay_timer = rng.next() as u8;
+    chip8.sound_timer = rng.next() as u8;
+    for key in chip8.keys.iter_mut() {
+        *key = rng.next() & 1 == 1;
+    }
+    chip8
+}
+
+fn check_invariants(chip8: &Chip8) {
+    assert!(chip8.stack_pointer <= chip8.stack.len());
+    assert!(chip8.framebuffer.iter().all(|p| *p <= 1));
+    assert!(chip8.dirty.iter().all(|r| r.x >= 0 && r.y >= 0 && r.x + r.width <= WIDTH as i32 && r.y + r.height <= HEIGHT as i32));
+}
+
+#[test]
+fn random_instructions_never_panic() {
+    let mut rng = Rng::new(0x8b0_7a70);
+    for _ in 0..50_000 {
+        let mut chip8 = random_state(&mut rng);
+        let opcode = rng.next() as u16;
+        let _ = exec(&mut chip8, opcode);
+        check_invariants(&chip8);
+    }
+}
+
+#[test]
+fn random_programs_never_panic() {
+    let mut rng = Rng::new(0xc41_9887);
+    for _ in 0..200 {
+        let mut chip8 = random_state(&mut rng);
+        chip8.stack_pointer = 0;
+        rng.fill(&mut chip8.memory[PROGRAM_START..]);
+        for _ in 0..2_000 {
+            if chip8.cycle().is_err() {
+                break;
+            }
+            check_invariants(&chip8);
+        }
+    }
+}
+
+#[test]
+fn decode_cache_sees_self_modifying_code() {
+    #[rustfmt::skip]
+    let rom = [
+        0xA2, 0x0C, // LD I, 0x20C
+        0x60, 0x63, // LD V0, 0x63
+        0x61, 0x07, // LD V1, 0x07
+        0x22, 0x0C, // CALL 0x20C
+        0xF1, 0x55, // LD [I], V1
+        0x12, 0x0C, // JP 0x20C
+        0x62, 0x01, // 0x20C: LD V2, 0x01, LD V3, 0x07 after the store
+        0x00, 0xEE, // RET
+    ];
+    for backend in Backend::ALL {
+        let mut chip8 = with_backend(*backend);
+        chip8.load_bytes(&rom).unwrap();
+        let mut left = 9;
+        while left > 0 {
+            left -= chip8.run(left).unwrap();
+        }
+        assert_eq!(chip8.registers[2], 0x01, "{}", backend.name());
+        assert_eq!(chip8.registers[3], 0x07, "{}", backend.name());
+    }
+}
+
+fn assert_same_state(a: &Chip8, b: &Chip8, what: &str) {
+    assert_eq!(a.program_counter, b.program_counter, "{}", what);
+    assert_eq!(a.registers, b.registers, "{}", what);
+    assert_eq!(a.index, b.index, "{}", what);
+    assert_eq!(a.stack_pointer, b.stack_pointer, "{}", what);
+    assert_eq!(a.stack, b.stack, "{}", what);
+    assert_eq!((a.delay_timer, a.sound_timer), (b.delay_timer, b.sound_timer), "{}", what);
+    assert_eq!(a.cycles, b.cycles, "{}", what);
+    assert_eq!(a.memory, b.memory, "{}", what);
+    assert_eq!(a.framebuffer, b.framebuffer, "{}", what);
+}
+
+// One compiled block if there is one at PC, otherwise a single cycle
+fn step_block(chip8: &mut Chip8) -> Result<u64, Error> {
+    #[cfg(target_arch = "x86_64")]
+    if let Some(ran) = chip8.run_block(64) {
+        return Ok(ran);
+    }
+    chip8.cycle().map(|_| 1)
+}
+
+// Runs `other` a block at a time next to a plain interpreter stepping
+// one instruction at a time, until either halts or RND comes up.
+fn run_against_interpreter(backend: Backend, rom: &[u8], cycles: u64, what: &str) {
+    let mut plain = Chip8::new();
+    let mut other = with_backend(backend);
+    plain.load_bytes(rom).unwrap();
+    other.load_bytes(rom).unwrap();
+    while plain.cycles < cycles {
+        // RND would diverge the two
+        if plain.peek_opcode() & 0xF000 == 0xC000 {
+            break;
+        }
+        let ran = match step_block(&mut other) {
+            Ok(ran) => ran,
+            Err(e) => {
+                assert_eq!(plain.cycle(), Err(e), "{}", what);
+                break;
+            }
+        };
+        for _ in 0..ran {
+            plain.cycle().unwrap();
+        }
+        assert_same_state(&plain, &other, what);
+    }
+}
+
+#[test]
+fn backends_match_interpreter() {
+    let mut rng = Rng::new(0xcac4e);
+    let mut rom = vec![0u8; MEMORY_SIZE - PROGRAM_START];
+    for _ in 0..100 {
+        rng.fill(&mut rom);
+        for backend in &Backend::ALL[1..] {
+            run_against_interpreter(*backend, &rom, 2_000, backend.name());
+        }
+    }
+}
+
+// Every rom under `roms/` on every backend against the interpreter
+#[test]
+fn golden_roms() {
+    let dir = concat!(env!("CARGO_MANIFEST_DIR"), "/roms");
+    for entry in std::fs::read_dir(dir).unwrap() {
+        let path = entry.unwrap().path();
+        let rom = std::fs::read(&path).unwrap();
+        for backend in &Backend::ALL[1..] {
+            let what = format!("{} ({})", path.display(), backend.name());
+            run_against_interpreter(*backend, &rom, 200_000, &what);
+        }
+    }
+}
+
+// Seeded stand-in for the cargo-fuzz target in fuzz/: random bytes through
+// `load_bytes` and `cycle`. Ignored by default, run with
+// `CHIP8_FUZZ_ITERS=1000000 cargo test fuzz -- --ignored` and try other
+// inputs with `CHIP8_FUZZ_SEED=<seed>`. The same seed replays a failure.
+#[test]
+#[ignore]
+fn fuzz_load_and_cycle() {
+    let env = |name: &str| std::env::var(name).ok().and_then(|v| v.parse::<u64>().ok());
+    let iters = env("CHIP8_FUZZ_ITERS").unwrap_or(100_000);
+    let seed = env("CHIP8_FUZZ_SEED").unwrap_or(1);
+
+    let mut rng = Rng::new(seed);
+    let mut rom = vec![0u8; MEMORY_SIZE];
+    for _ in 0..iters {
+        let len = rng.below(rom.len() + 1);
+        rng.fill(&mut rom[..len]);
+
+        let mut chip8 = Chip8::new();
+        if chip8.load_bytes(&rom[..len]).is_err() {
+            assert!(len > MEMORY_SIZE - PROGRAM_START);
+            continue;
+        }
+        for key in chip8.keys.iter_mut() {
+            *key = rng.next() & 1 == 1;
+        }
+        for _ in 0..1_000 {
+            if chip8.cycle().is_err() {
+                break;
+            }
+            check_invariants(&chip8);
+        }
+    }
+}
@@ -0,0 +1,225 @@
+use super::*;
+
+/// Instructions per tick are doubled/halved within this range
+pub(crate) const MAX_SPEED: u64 = 1024;
+/// Speed multiplier while fast forward is held
+pub(crate) const FAST_FORWARD: u64 = 8;
+
+impl App {
+    // Instructions to run for `ticks` timer ticks
+    pub(crate) fn budget(&mut self, ticks: u64) -> u64 {
+        if self.focus_paused() {
+            // timers only tick with instructions, so the sound stops too
+            return 0;
+        }
+        if self.paused {
+            // frame advance runs whole ticks, never more than came in
+            let ticks = std::mem::take(&mut self.advance).min(ticks);
+            return ticks * self.speed;
+        }
+        match self.fast_forward {
+            true => ticks * self.speed * FAST_FORWARD,
+            false => ticks * self.speed,
+        }
+    }
+
+    pub(crate) fn toggle_pause(&mut self, conn: &Connection) {
+        self.paused = !self.paused;
+        self.advance = 0;
+        self.update_title(conn);
+    }
+
+    // Paused by `--pause-unfocused`, separate from the user's pause so
+    // focusing the window again doesn't resume a game paused on purpose
+    pub(crate) fn focus_paused(&self) -> bool {
+        self.pause_unfocused && !self.focused
+    }
+
+    pub(crate) fn set_focus(&mut self, conn: &Connection, focused: bool) {
+        self.focused = focused;
+        self.update_title(conn);
+    }
+
+    // Screen blanking is off while a game runs in the focused window, any
+    // pause, halt or focus change lets it back on
+    pub(crate) fn update_idle_inhibit(&mut self, conn: &Connection) {
+        let running = self.halted.is_none() && !self.paused && self.focused;
+        if running == self.idle_inhibitor.is_some() {
+            return;
+        }
+        match self.idle_inhibitor.take() {
+            Some(inhibitor) => inhibitor.destroy(conn.writer()),
+            None => {
+                let (Some(mgr), Some(wl_surface)) = (self.idle_inhibit_mgr.as_ref(), self.base_surface.as_ref()) else {
+                    return;
+                };
+                self.idle_inhibitor = Some(mgr.create_inhibitor(conn.writer(), wl_surface));
+            }
+        }
+    }
+
+    // One tick's worth of instructions, only while paused
+    pub(crate) fn advance_frame(&mut self) {
+        if self.paused {
+            self.advance += 1;
+        }
+    }
+
+    // Doubles or halves the instructions run per tick
+    pub(crate) fn change_speed(&mut self, conn: &Connection, faster: bool) {
+        self.speed = match faster {
+            true => (self.speed * 2).min(MAX_SPEED),
+            false => (self.speed / 2).max(1),
+        };
+        self.osd.toast(format!("Speed x{}", self.speed));
+        self.update_title(conn);
+    }
+
+    pub(crate) fn set_fast_forward(&mut self, conn: &Connection, held: bool) {
+        if self.fast_forward != held {
+            self.fast_forward = held;
+            self.update_title(conn);
+        }
+    }
+
+    // Fresh machine with the same font and backend, the rom is read again
+    // so it can be rebuilt in between
+    pub(crate) fn reset(&mut self, conn: &Connection) {
+        let rom_path = self.rom_path.clone();
+        match self.restart(conn, &rom_path) {
+            Ok(()) => self.osd.toast("Reset"),
+            Err(e) => log!(ERR, "Reset failed, '{}' - {}", rom_path, e),
+        }
+    }
+
+    // Fresh machine running `rom_path`, the old one keeps going if it
+    // doesn't load
+    pub(crate) fn restart(&mut self, conn: &Connection, rom_path: &str) -> std::io::Result<()> {
+        let mut chip8 = chip8::Chip8::new();
+        chip8.set_font(&self.chip8.font, self.chip8.font_base).map_err(std::io::Error::other)?;
+        chip8.load(rom_path)?;
+        chip8.set_backend(self.backend)?;
+        chip8.need_redraw = true;
+        chip8.dirty.push(render::Rect { x: 0, y: 0, width: chip8::WIDTH as i32, height: chip8::HEIGHT as i32 });
+        self.chip8 = chip8;
+        self.rom_path = rom_path.to_string();
+        self.halted = None;
+        self.update_title(conn);
+        Ok(())
+    }
+
+    pub(crate) fn rom_name(&self) -> String {
+        std::path::Path::new(&self.rom_path)
+            .file_stem()
+            .map_or(String::new(), |n| n.to_string_lossy().into_owned())
+    }
+
+    pub(crate) fn toggle_stats(&mut self) {
+        self.osd.stats = !self.osd.stats;
+    }
+
+    // Lines that stay on screen for as long as they're true
+    pub(crate) fn osd_status(&self) -> Vec<String> {
+        let mut status = Vec::new();
+        if self.osd.stats {
+            let speed = match self.fast_forward {
+                true => self.speed * FAST_FORWARD,
+                false => self.speed,
+            };
+            status.push(format!("FPS {} x{}", self.osd.fps(), speed));
+        }
+        if let Some(e) = self.halted {
+            status.push(format!("Halted: {}", e));
+        } else if self.paused || self.focus_paused() {
+            status.push("Paused".into());
+        }
+        if self.recorder.is_some() {
+            status.push("Recording".into());
+        }
+        status
+    }
+
+    pub(crate) fn toggle_recording(&mut self) {
+        if self.recorder.is_some() {
+            self.stop_recording();
+            return;
+        }
+        let dir = screenshot::user_dir_or_home("XDG_VIDEOS_DIR");
+        match screenshot::create_unique(&dir, &self.rom_name(), self.record_format.name()) {
+            Ok((_, path)) => self.start_recording(&path.to_string_lossy()),
+            Err(e) => {
+                log!(ERR, "Recording - {}", e);
+                self.osd.toast("Recording failed");
+            }
+        }
+    }
+
+    pub(crate) fn start_recording(&mut self, path: &str) {
+        let res = record::Format::from_path(path)
+            .map_err(std::io::Error::other)
+            .and_then(|format| Ok((format, std::fs::File::create(path)?)))
+            .and_then(|(format, file)| record::Recorder::new(std::io::BufWriter::new(file), format));
+        match res {
+            Ok(recorder) => {
+                log!(DEBUG, "Recording to '{}'", path);
+                self.recorder = Some((recorder, std::time::Instant::now()));
+            }
+            Err(e) => {
+                log!(ERR, "'{}' - {}", path, e);
+                self.osd.toast("Recording failed");
+            }
+        }
+    }
+
+    pub fn stop_recording(&mut self) {
+        let Some((recorder, _)) = self.recorder.take() else {
+            return;
+        };
+        match recorder.finish() {
+            Ok(_) => self.osd.toast("Recording saved"),
+            Err(e) => {
+                log!(ERR, "Recording - {}", e);
+                self.osd.toast("Recording failed");
+            }
+        }
+    }
+
+    // Catches the video up with the clock, whatever is on screen stays up
+    // until there's something new
+    pub(crate) fn record_frames(&mut self) {
+        let Some((recorder, start)) = self.recorder.as_mut() else {
+            return;
+        };
+        let due = start.elapsed().as_micros() as u64 * record::FPS / 1_000_000;
+        if let Err(e) = recorder.frame(&self.display.colors, due.saturating_sub(recorder.frames)) {
+            log!(ERR, "Recording stopped: {}", e);
+            self.osd.toast("Recording failed");
+            self.recorder = None;
+        }
+    }
+
+    pub(crate) fn update_title(&mut self, conn: &Connection) {
+        let mut title = String::from("eightpotatochips");
+        let rom_name = self.rom_name();
+        if !rom_name.is_empty() {
+            title.push_str(&format!(" - {}", rom_name));
+        }
+        if self.halted.is_some() {
+            title.push_str(" - Halted");
+        } else if self.paused || self.focus_paused() {
+            title.push_str(" - Paused");
+        }
+        if self.fast_forward {
+            title.push_str(&format!(" - Fast forward x{}", self.speed * FAST_FORWARD));
+        } else if self.speed != 1 {
+            title.push_str(&format!(" - x{}", self.speed));
+        }
+        if title == self.title {
+            return;
+        }
+        if let Some(xdg_toplevel) = self.xdg_toplevel.as_ref() {
+            xdg_toplevel.set_title(conn.writer(), &title);
+        }
+        self.title = title;
+    }
+}
@@ -0,0 +1,159 @@
+// Title bar for compositors that won't decorate the window themselves
+// (GNOME, anything without xdg-decoration). It's part of the buffer and the
+// window geometry, so configure sizes include it.
+use super::*;
+
+/// Title bar height, also the width of its buttons
+pub(crate) const TITLE_H: i32 = 24;
+/// Resize handles along the window edges, inside the window
+const BORDER: i32 = 6;
+/// Font pixel size at 1x
+const TEXT_SCALE: i32 = 2;
+/// Bar background brightness of the foreground colour
+const BAR: u32 = 40;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Hit {
+    Close,
+    Maximize,
+    Title,
+    /// xdg_toplevel.resize_edge
+    Edge(u32),
+    Content,
+}
+
+// What's under `x`, `y` in a `width`x`height` window with a title bar
+pub(crate) fn hit(width: i32, height: i32, x: i32, y: i32, resizable: bool) -> Hit {
+    if resizable {
+        let edge = [
+            (y < BORDER, xdg_toplevel::ResizeEdge::Top),
+            (y >= height - BORDER, xdg_toplevel::ResizeEdge::Bottom),
+            (x < BORDER, xdg_toplevel::ResizeEdge::Left),
+            (x >= width - BORDER, xdg_toplevel::ResizeEdge::Right),
+        ]
+        .iter()
+        .filter(|(on, _)| *on)
+        .fold(0, |edges, (_, edge)| edges | *edge as u32);
+        if edge != 0 {
+            return Hit::Edge(edge);
+        }
+    }
+    if y >= TITLE_H {
+        return Hit::Content;
+    }
+    // buttons from the right
+    match (width - 1 - x) / TITLE_H {
+        0 => Hit::Close,
+        1 => Hit::Maximize,
+        _ => Hit::Title,
+    }
+}
+
+// Repaints the part of the title bar inside `clip`, `bar_h` is `TITLE_H`
+// in buffer pixels
+pub(crate) fn draw(canvas: &mut render::Canvas<'_>, palette: &palette::Palette, title: &str, bar_h: i32, clip: render::Rect) {
+    let width = canvas.width as i32;
+    let bar = render::Rect { x: 0, y: 0, width, height: bar_h }.intersect(&clip);
+    if bar.is_empty() {
+        return;
+    }
+    let (background, text) = (filter::shade(palette[1], BAR), palette[1]);
+    for y in bar.y..bar.y + bar.height {
+        let start = (y * width + bar.x) as usize;
+        canvas.pixels[start..start + bar.width as usize].fill(background);
+    }
+
+    // whole font pixels only, anything else smears
+    let scale = (bar_h * TEXT_SCALE / TITLE_H).max(1);
+    let glyph_y = (bar_h - osd::GLYPH_H * scale) / 2;
+    // title stops short of the buttons
+    let title_clip = render::Rect { x: 0, y: 0, width: width - 2 * bar_h, height: bar_h }.intersect(&bar);
+    osd::draw_text(canvas, glyph_y * 2, glyph_y, scale, title, text, title_clip);
+
+    let close_x = width - bar_h + (bar_h - osd::GLYPH_W * scale) / 2;
+    osd::draw_text(canvas, close_x, glyph_y, scale, "X", text, bar);
+    // maximize is an outlined square
+    let side = osd::GLYPH_H * scale;
+    let x = width - 2 * bar_h + (bar_h - side) / 2;
+    let outline = [
+        render::Rect { x, y: glyph_y, width: side, height: scale },
+        render::Rect { x, y: glyph_y + side - scale, width: side, height: scale },
+        render::Rect { x, y: glyph_y, width: scale, height: side },
+        render::Rect { x: x + side - scale, y: glyph_y, width: scale, height: side },
+    ];
+    for line in outline {
+        let line = line.intersect(&bar);
+        for y in line.y..line.y + line.height {
+            let start = (y * width + line.x) as usize;
+            canvas.pixels[start..start + line.width as usize].fill(text);
+        }
+    }
+}
+
+impl App {
+    // Drawing our own title bar: the compositor won't, and there's no
+    // bar while fullscreen
+    pub(crate) fn csd(&self) -> bool {
+        self.deco_mode != Some(zxdg_toplevel_decoration_v1::Mode::ServerSide) && !self.win_state.fullscreen
+    }
+
+    // Below the title bar, if there is one, in buffer pixels
+    pub(crate) fn content_area(&self, width: i32, height: i32) -> render::Rect {
+        let bar = if self.csd() { self.to_device(TITLE_H).min(height) } else { 0 };
+        render::Rect { x: 0, y: bar, width, height: height - bar }
+    }
+
+    // Left click at the pointer position. True when the title bar took it.
+    pub(crate) fn csd_click(&mut self, conn: &Connection, serial: u32) -> bool {
+        if !self.csd() {
+            return false;
+        }
+        let (x, y) = self.pointer_pos;
+        let resizable = !self.win_state.constrained();
+        let hit = hit(self.win_width, self.win_height, x, y, resizable);
+        let (Some(xdg_toplevel), Some(wl_seat)) = (self.xdg_toplevel.as_ref(), self.wl_seat.as_ref()) else {
+            return false;
+        };
+        match hit {
+            Hit::Close => self.exit = true,
+            Hit::Maximize => self.toggle_maximized(conn),
+            Hit::Title => xdg_toplevel.r#move(conn.writer(), wl_seat, serial),
+            Hit::Edge(edges) => xdg_toplevel.resize(conn.writer(), wl_seat, serial, edges),
+            Hit::Content => return false,
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn hit_regions() {
+        let (w, h) = (640, 344);
+        assert_eq!(hit(w, h, 639 - BORDER, 10, true), Hit::Close);
+        assert_eq!(hit(w, h, 640 - TITLE_H - 1, 10, true), Hit::Maximize);
+        assert_eq!(hit(w, h, 300, 10, true), Hit::Title);
+        assert_eq!(hit(w, h, 300, 100, true), Hit::Content);
+        let top_left = xdg_toplevel::ResizeEdge::TopLeft as u32;
+        assert_eq!(hit(w, h, 0, 0, true), Hit::Edge(top_left));
+        assert_eq!(hit(w, h, 300, 343, true), Hit::Edge(xdg_toplevel::ResizeEdge::Bottom as u32));
+        // maximized windows don't resize
+        assert_eq!(hit(w, h, 639, 0, false), Hit::Close);
+        assert_eq!(hit(w, h, 300, 343, false), Hit::Content);
+    }
+
+    #[test]
+    fn bar_stays_in_its_strip() {
+        let palette = [0x000000, 0xFFFFFF, 0xFF0000, 0x00FF00];
+        let mut pixels = vec![7u32; 200 * 40];
+        let mut canvas = render::Canvas { pixels: &mut pixels, width: 200, height: 40 };
+        let full = render::Rect { x: 0, y: 0, width: 200, height: 40 };
+        draw(&mut canvas, &palette, "PONG", TITLE_H, full);
+        let bar = (TITLE_H * 200) as usize;
+        assert!(pixels[bar..].iter().all(|p| *p == 7));
+        assert!(pixels[..bar].iter().all(|p| *p == 0xFFFFFF || *p == filter::shade(0xFFFFFF, BAR)));
+        assert!(pixels[..bar].contains(&0xFFFFFF));
+    }
+}
@@ -0,0 +1,226 @@
+// What the framebuffer looks like on screen. Games erase sprites by drawing
+// them again (XOR) and redraw them right after, which flickers unless pixels
+// are kept around for a bit.
+use std::time::{Duration, Instant};
+
+use super::chip8::{HEIGHT, WIDTH};
+use super::palette::Palette;
+use super::render::Rect;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Persistence {
+    /// Pixels go dark the moment they're cleared
+    #[default]
+    Off,
+    /// Cleared pixels fade out over a few frames
+    Phosphor,
+    /// Anything lit in this frame or the one before
+    Blend,
+}
+
+impl Persistence {
+    pub const ALL: &[Persistence] = &[Self::Off, Self::Phosphor, Self::Blend];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Off => "off",
+            Self::Phosphor => "phosphor",
+            Self::Blend => "blend",
+        }
+    }
+}
+
+pub const DEFAULT_DECAY: u8 = 6;
+const LIT: u8 = u8::MAX;
+/// Phosphor fades in sixtieths of a second whatever the refresh rate
+const FADE_FRAME: Duration = Duration::from_nanos(1_000_000_000 / 60);
+
+#[derive(Debug)]
+pub(crate) struct Display {
+    mode: Persistence,
+    /// Intensity lost per `FADE_FRAME`
+    step: u8,
+    /// Last fade step while anything's fading
+    faded_at: Option<Instant>,
+    intensity: Vec<u8>,
+    /// Pixel value last lit, so a fading pixel keeps its plane colour
+    glow: Vec<u8>,
+    previous: Vec<u8>,
+    previous_dirty: Vec<Rect>,
+    /// Xrgb8888, one per framebuffer pixel
+    pub colors: Vec<u32>,
+}
+
+impl Display {
+    // `decay` is how many sixtieths of a second a cleared pixel takes to go dark
+    pub fn new(mode: Persistence, decay: u8) -> Self {
+        Self {
+            mode,
+            step: LIT / decay.max(1),
+            faded_at: None,
+            intensity: vec![0; WIDTH * HEIGHT],
+            glow: vec![0; WIDTH * HEIGHT],
+            previous: vec![0; WIDTH * HEIGHT],
+            previous_dirty: Vec::new(),
+            colors: vec![0; WIDTH * HEIGHT],
+        }
+    }
+
+    // Works out `colors` for the next frame. Pixels that change on their
+    // own (fading, last frame's pixels going away) are added to `dirty`.
+    // Fading goes by `now`, not by how often this is called.
+    // Returns whether the picture keeps changing without new draws.
+    pub fn compose(&mut self, framebuffer: &[u8], palette: &Palette, dirty: &mut Vec<Rect>, now: Instant) -> bool {
+        match self.mode {
+            Persistence::Off => {
+                for (color, pix) in self.colors.iter_mut().zip(framebuffer) {
+                    *color = palette[(pix & 3) as usize];
+                }
+                false
+            }
+            Persistence::Blend => {
+                for ((color, pix), prev) in self.colors.iter_mut().zip(framebuffer).zip(&self.previous) {
+                    *color = palette[((pix | prev) & 3) as usize];
+                }
+                self.previous.copy_from_slice(framebuffer);
+                // whatever changed last frame is still on screen, and goes now
+                let changed = dirty.clone();
+                dirty.append(&mut self.previous_dirty);
+                let more = !changed.is_empty();
+                self.previous_dirty = changed;
+                more
+            }
+            Persistence::Phosphor => {
+                // whole fade frames since the last step, the rest carries over
+                let frames = self.faded_at.map_or(0, |t| {
+                    now.saturating_duration_since(t).as_nanos() / FADE_FRAME.as_nanos()
+                });
+                if let Some(t) = self.faded_at.as_mut() {
+                    *t += FADE_FRAME * frames as u32;
+                }
+                let loss = (self.step as u128 * frames).min(LIT as u128) as u8;
+                let mut fading = None::<Rect>;
+                for (i, pix) in framebuffer.iter().enumerate() {
+                    let pix = pix & 3;
+                    if pix != 0 {
+                        self.intensity[i] = LIT;
+                        self.glow[i] = pix;
+                    } else if self.intensity[i] > 0 {
+                        self.intensity[i] = self.intensity[i].saturating_sub(loss);
+                        let (x, y) = ((i % WIDTH) as i32, (i / WIDTH) as i32);
+                        fading = Some(match fading {
+                            None => Rect { x, y, width: 1, height: 1 },
+                            Some(r) => {
+                                let (x0, y0) = (r.x.min(x), r.y.min(y));
+                                let x1 = (r.x + r.width).max(x + 1);
+                                let y1 = (r.y + r.height).max(y + 1);
+                                Rect { x: x0, y: y0, width: x1 - x0, height: y1 - y0 }
+                            }
+                        });
+                    }
+                    let lit = palette[self.glow[i] as usize];
+                    self.colors[i] = mix(palette[0], lit, self.intensity[i]);
+                }
+                dirty.extend(fading);
+                // a pixel that just went out starts a full fade frame from now
+                self.faded_at = fading.and(self.faded_at.or(Some(now)));
+                fading.is_some()
+            }
+        }
+    }
+}
+
+// `bg` to `fg` by `amount` / 255, per channel
+fn mix(bg: u32, fg: u32, amount: u8) -> u32 {
+    let amount = amount as u32;
+    let channel = |shift: u32| {
+        let (b, f) = ((bg >> shift) & 0xFF, (fg >> shift) & 0xFF);
+        ((b * (255 - amount) + f * amount) / 255) << shift
+    };
+    channel(16) | channel(8) | channel(0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PALETTE: Palette = [0x000000, 0xFFFFFF, 0xFF0000, 0x00FF00];
+
+    #[test]
+    fn phosphor_fades_out() {
+        let mut display = Display::new(Persistence::Phosphor, 3);
+        let mut fb = vec![0u8; WIDTH * HEIGHT];
+        let mut dirty = Vec::new();
+        let mut now = Instant::now();
+        fb[WIDTH + 2] = 1;
+        assert!(!display.compose(&fb, &PALETTE, &mut dirty, now));
+        assert_eq!(display.colors[WIDTH + 2], 0xFFFFFF);
+
+        fb[WIDTH + 2] = 0;
+        assert!(display.compose(&fb, &PALETTE, &mut dirty, now));
+        assert_eq!(display.colors[WIDTH + 2], 0xFFFFFF);
+        let mut frames = 0;
+        while display.colors[WIDTH + 2] != 0 {
+            now += FADE_FRAME;
+            frames += 1;
+            assert!(display.compose(&fb, &PALETTE, &mut dirty, now));
+            assert!(display.colors[WIDTH + 2] < 0xFFFFFF);
+        }
+        assert_eq!(frames, 3);
+        // dark, nothing left to redraw
+        assert!(!display.compose(&fb, &PALETTE, &mut dirty, now));
+        assert!(dirty.iter().all(|r| *r == Rect { x: 2, y: 1, width: 1, height: 1 }));
+    }
+
+    #[test]
+    fn phosphor_ignores_refresh_rate() {
+        let fade = |refresh: u32| {
+            let mut display = Display::new(Persistence::Phosphor, DEFAULT_DECAY);
+            let mut fb = vec![0u8; WIDTH * HEIGHT];
+            let mut dirty = Vec::new();
+            let start = Instant::now();
+            fb[0] = 1;
+            display.compose(&fb, &PALETTE, &mut dirty, start);
+            fb[0] = 0;
+            let mut frame = 0;
+            while display.compose(&fb, &PALETTE, &mut dirty, start + Duration::from_secs(1) * frame / refresh) {
+                frame += 1;
+            }
+            Duration::from_secs(1) * frame / refresh
+        };
+        let (at_60, at_144) = (fade(60), fade(144));
+        assert!(at_60.abs_diff(at_144) < Duration::from_secs(1) / 60, "{:?} {:?}", at_60, at_144);
+        assert!(at_60 >= FADE_FRAME * DEFAULT_DECAY as u32);
+    }
+
+    #[test]
+    fn blend_ors_the_last_two_frames() {
+        let mut display = Display::new(Persistence::Blend, DEFAULT_DECAY);
+        let mut fb = vec![0u8; WIDTH * HEIGHT];
+        let mut dirty = Vec::new();
+        fb[0] = 1;
+        dirty.push(Rect { x: 0, y: 0, width: 1, height: 1 });
+        assert!(display.compose(&fb, &PALETTE, &mut dirty, Instant::now()));
+
+        fb[0] = 0;
+        fb[1] = 2;
+        dirty.clear();
+        dirty.push(Rect { x: 0, y: 0, width: 2, height: 1 });
+        display.compose(&fb, &PALETTE, &mut dirty, Instant::now());
+        assert_eq!(display.colors[..2], [0xFFFFFF, 0xFF0000]);
+        // last frame's change comes along so it gets cleared next time
+        assert_eq!(dirty.len(), 2);
+
+        dirty.clear();
+        assert!(!display.compose(&fb, &PALETTE, &mut dirty, Instant::now()));
+        assert_eq!(display.colors[..2], [0x000000, 0xFF0000]);
+        assert_eq!(dirty, [Rect { x: 0, y: 0, width: 2, height: 1 }]);
+    }
+
+    #[test]
+    fn mix_channels() {
+        assert_eq!(mix(0x000000, 0xFFFFFF, 0), 0x000000);
+        assert_eq!(mix(0x000000, 0xFFFFFF, 255), 0xFFFFFF);
+        assert_eq!(mix(0x204060, 0x204060, 100), 0x204060);
+    }
+}
@@ -0,0 +1,277 @@
+// Dropping a rom file on the window runs it. File managers offer dropped
+// files as a `text/uri-list`, only the first `file://` one counts. The list
+// comes through a pipe the main loop polls, emulation goes on meanwhile.
+// There's no rom database to look settings up in, a dropped rom runs with
+// the options the emulator was started with.
+use std::io::Read;
+
+use super::*;
+
+const URI_LIST: &str = "text/uri-list";
+/// Way more than any sane drop, the source is stuck or malicious past it
+const MAX_URI_LIST: usize = 64 * 1024;
+
+#[derive(Debug)]
+pub(crate) struct Offer {
+    wl_data_offer: wl_data_offer::WlDataOffer,
+    /// Offered as `URI_LIST`
+    uri_list: bool,
+}
+
+/// A drop's uri list on its way in
+#[derive(Debug)]
+pub(crate) struct DropRead {
+    wl_data_offer: wl_data_offer::WlDataOffer,
+    reader: std::io::PipeReader,
+    list: Vec<u8>,
+}
+
+impl DropRead {
+    pub fn fd(&self) -> i32 {
+        self.reader.as_raw_fd()
+    }
+}
+
+impl App {
+    #[rustfmt::skip]
+    pub(crate) fn on_data_device(&mut self, conn: &Connection, event: WlEvent<'_>) {
+        let Some(data_device) = self.data_device.as_ref() else {
+            return;
+        };
+        match data_device.parse_event(conn.reader(), event) {
+            // mime types come in right after, before enter or selection
+            wl_data_device::Event::DataOffer { id } => {
+                let wl_data_offer = wl_data_offer::WlDataOffer::from_id(id);
+                self.add_callback(id, Self::on_data_offer);
+                self.offers.push(Offer { wl_data_offer, uri_list: false });
+            },
+            wl_data_device::Event::Enter { serial, id, .. } => {
+                self.drag_offer = Some(id);
+                let Some(offer) = self.offers.iter().find(|o| o.wl_data_offer.id() == id) else {
+                    return;
+                };
+                let copy = wl_data_device_manager::DND_ACTION_COPY;
+                match offer.uri_list {
+                    true => {
+                        offer.wl_data_offer.accept(conn.writer(), serial, Some(URI_LIST));
+                        offer.wl_data_offer.set_actions(conn.writer(), copy, copy);
+                    }
+                    false => offer.wl_data_offer.accept(conn.writer(), serial, None),
+                }
+            },
+            wl_data_device::Event::Leave => {
+                if let Some(id) = self.drag_offer.take() {
+                    self.drop_offer(conn, id);
+                }
+            },
+            wl_data_device::Event::Drop => {
+                if let Some(id) = self.drag_offer.take() {
+                    self.receive_drop(conn, id);
+                }
+            },
+            // the clipboard, nothing we'd paste
+            wl_data_device::Event::Selection { id } => self.drop_offer(conn, id),
+            wl_data_device::Event::Motion { .. } => {},
+        }
+    }
+
+    #[rustfmt::skip]
+    pub(crate) fn on_data_offer(&mut self, conn: &Connection, event: WlEvent<'_>) {
+        let id = event.header.id;
+        let Some(offer) = self.offers.iter_mut().find(|o| o.wl_data_offer.id() == id) else {
+            return;
+        };
+        match offer.wl_data_offer.parse_event(conn.reader(), event) {
+            wl_data_offer::Event::Offer { mime_type } => offer.uri_list |= mime_type == URI_LIST,
+            wl_data_offer::Event::SourceActions { .. } => {},
+            wl_data_offer::Event::Action { .. } => {},
+        }
+    }
+
+    fn drop_offer(&mut self, conn: &Connection, id: u32) {
+        if let Some(i) = self.offers.iter().position(|o| o.wl_data_offer.id() == id) {
+            self.offers.swap_remove(i).wl_data_offer.destroy(conn.writer());
+        }
+    }
+
+    // Asks for the list, the offer stays around until all of it is in
+    fn receive_drop(&mut self, conn: &Connection, id: u32) {
+        let Some(i) = self.offers.iter().position(|o| o.wl_data_offer.id() == id) else {
+            return;
+        };
+        let offer = self.offers.swap_remove(i);
+        if !offer.uri_list {
+            offer.wl_data_offer.destroy(conn.writer());
+            return;
+        }
+        let (reader, writer) = match nonblocking_pipe() {
+            Ok(pipe) => pipe,
+            Err(e) => {
+                log!(ERR, "Drop - {}", e);
+                self.osd.toast("Drop failed");
+                offer.wl_data_offer.destroy(conn.writer());
+                return;
+            }
+        };
+        offer.wl_data_offer.receive(conn.writer(), URI_LIST, writer.as_raw_fd());
+        // only the source writes from here, the list ends when it closes.
+        // The request has to go out before our end does.
+        if let Err(e) = conn.flush() {
+            log!(WARNING, "Drop - {}", e);
+        }
+        drop(writer);
+        // a drop still coming in gives way to the new one
+        if let Some(old) = self.drop_read.take() {
+            old.wl_data_offer.destroy(conn.writer());
+        }
+        let wl_data_offer = offer.wl_data_offer;
+        self.drop_read = Some(DropRead { wl_data_offer, reader, list: Vec::new() });
+    }
+
+    // Reads whatever the source has written so far, the rom loads once it
+    // closes its end
+    pub(crate) fn read_drop(&mut self, conn: &Connection) {
+        let Some(read) = self.drop_read.as_mut() else {
+            return;
+        };
+        let res = match read_available(&mut read.reader, &mut read.list) {
+            Ok(false) => return,
+            Ok(true) => Ok(()),
+            Err(e) => Err(e),
+        };
+        let Some(read) = self.drop_read.take() else {
+            return;
+        };
+        read.wl_data_offer.finish(conn.writer());
+        read.wl_data_offer.destroy(conn.writer());
+
+        let list = res.and_then(|()| {
+            String::from_utf8(read.list).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+        });
+        let path = match list {
+            Ok(list) => match first_file(&list) {
+                Some(path) => path,
+                None => {
+                    self.osd.toast("Only files can be dropped");
+                    return;
+                }
+            },
+            Err(e) => {
+                log!(ERR, "Drop - {}", e);
+                self.osd.toast("Drop failed");
+                return;
+            }
+        };
+        match self.restart(conn, &path) {
+            Ok(()) => self.osd.toast(format!("Loaded {}", self.rom_name())),
+            Err(e) => {
+                log!(ERR, "'{}' - {}", path, e);
+                self.osd.toast(format!("Can't load: {}", e));
+            }
+        }
+    }
+}
+
+// A pipe whose read end never blocks, poll says when there's something
+fn nonblocking_pipe() -> std::io::Result<(std::io::PipeReader, std::io::PipeWriter)> {
+    let (reader, writer) = std::io::pipe()?;
+    unsafe {
+        let flags = syscall(libc::fcntl(reader.as_raw_fd(), libc::F_GETFL))?;
+        syscall(libc::fcntl(reader.as_raw_fd(), libc::F_SETFL, flags | libc::O_NONBLOCK))?;
+    }
+    Ok((reader, writer))
+}
+
+// Appends what's in the pipe to `list`, true once the writer has closed
+fn read_available(reader: &mut impl Read, list: &mut Vec<u8>) -> std::io::Result<bool> {
+    let mut chunk = [0u8; 4096];
+    loop {
+        match reader.read(&mut chunk) {
+            Ok(0) => return Ok(true),
+            Ok(n) if list.len() + n > MAX_URI_LIST => {
+                return Err(std::io::Error::new(std::io::ErrorKind::InvalidData, "uri list too long"));
+            }
+            Ok(n) => list.extend_from_slice(&chunk[..n]),
+            Err(e) if e.kind() == std::io::ErrorKind::WouldBlock => return Ok(false),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+// Path of the first local file in a uri list
+fn first_file(list: &str) -> Option<String> {
+    list.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .find_map(|uri| {
+            let rest = uri.strip_prefix("file://")?;
+            // an empty host or localhost, other machines' files aren't here
+            let path = match rest.find('/')? {
+                0 => rest,
+                i if &rest[..i] == "localhost" => &rest[i..],
+                _ => return None,
+            };
+            percent_decode(path)
+        })
+}
+
+fn percent_decode(s: &str) -> Option<String> {
+    let mut bytes = Vec::with_capacity(s.len());
+    let mut rest = s.as_bytes();
+    while let Some((&b, tail)) = rest.split_first() {
+        if b == b'%' {
+            let hex = std::str::from_utf8(tail.get(..2)?).ok()?;
+            bytes.push(u8::from_str_radix(hex, 16).ok()?);
+            rest = &tail[2..];
+        } else {
+            bytes.push(b);
+            rest = tail;
+        }
+    }
+    String::from_utf8(bytes).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn uri_lists() {
+        let list = "# from a file manager\r\nhttps://example.com/pong.ch8\r\nfile:///home/me/My%20Roms/pong.ch8\r\n";
+        assert_eq!(first_file(list).as_deref(), Some("/home/me/My Roms/pong.ch8"));
+        assert_eq!(first_file("file://localhost/tmp/a.ch8").as_deref(), Some("/tmp/a.ch8"));
+        assert_eq!(first_file("file://otherhost/tmp/a.ch8"), None);
+        assert_eq!(first_file("file:///tmp/bad%2"), None);
+        assert_eq!(first_file(""), None);
+    }
+
+    #[test]
+    fn reads_dont_wait_for_the_writer() {
+        use std::io::Write;
+        let (mut reader, mut writer) = nonblocking_pipe().unwrap();
+        let mut list = Vec::new();
+        assert!(!read_available(&mut reader, &mut list).unwrap());
+        writer.write_all(b"file:///tmp/").unwrap();
+        assert!(!read_available(&mut reader, &mut list).unwrap());
+        writer.write_all(b"a.ch8\r\n").unwrap();
+        drop(writer);
+        assert!(read_available(&mut reader, &mut list).unwrap());
+        assert_eq!(list, b"file:///tmp/a.ch8\r\n");
+
+        // more than the pipe holds, so the writer waits on us
+        let (mut reader, mut writer) = nonblocking_pipe().unwrap();
+        let source = std::thread::spawn(move || writer.write_all(&[b'#'; MAX_URI_LIST + 1]));
+        let mut list = Vec::new();
+        let res = loop {
+            match read_available(&mut reader, &mut list) {
+                Ok(false) => continue,
+                res => break res,
+            }
+        };
+        assert!(res.is_err());
+        assert!(list.len() <= MAX_URI_LIST);
+        drop(reader);
+        let _ = source.join();
+    }
+}
@@ -0,0 +1,181 @@
+// CRT look, all on the CPU. Everything except bloom is just a brightness
+// factor for every canvas row times one for every column, worked out once per
+// window size, so drawing stays a multiply per pixel and rows that come out
+// the same are still copied instead of redrawn.
+use super::render::Rect;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Filter {
+    /// Darkens the bottom third of every emulated pixel row
+    Scanlines,
+    /// Thin dark outline around every emulated pixel
+    Grid,
+    /// Lit pixels bleed a little light into their neighbours
+    Bloom,
+    /// Darker towards the edges
+    Vignette,
+}
+
+impl Filter {
+    pub const ALL: &[Filter] = &[Self::Scanlines, Self::Grid, Self::Bloom, Self::Vignette];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Scanlines => "scanlines",
+            Self::Grid => "grid",
+            Self::Bloom => "bloom",
+            Self::Vignette => "vignette",
+        }
+    }
+}
+
+// Comma separated filter names, `none` for no filters
+pub fn parse_list(s: &str) -> Result<Vec<Filter>, String> {
+    if s == "none" {
+        return Ok(Vec::new());
+    }
+    s.split(',')
+        .map(|name| {
+            Filter::ALL
+                .iter()
+                .find(|f| f.name() == name.trim())
+                .copied()
+                .ok_or_else(|| format!("Unknown filter '{}'", name))
+        })
+        .collect()
+}
+
+/// Full brightness
+pub const ONE: u32 = 256;
+const SCANLINE: u32 = 168;
+const GRID: u32 = 200;
+/// Brightness lost in the very corners, per axis
+const VIGNETTE: u32 = 72;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct Masks {
+    pub rows: Vec<u32>,
+    pub columns: Vec<u32>,
+    /// What they were made for
+    pub width: i32,
+    pub height: i32,
+    pub dst: Rect,
+}
+
+impl Masks {
+    // `None` when no filter changes brightness
+    pub fn new(filters: &[Filter], width: i32, height: i32, dst: Rect, src_w: usize, src_h: usize) -> Option<Self> {
+        let has = |f| filters.contains(&f);
+        if !has(Filter::Scanlines) && !has(Filter::Grid) && !has(Filter::Vignette) {
+            return None;
+        }
+        let axis = |len: i32, start: i32, size: i32, src: usize, scanlines: bool| {
+            let src = src as i32;
+            // emulated pixels this small don't have room for lines
+            let lines = size / src >= 3;
+            (0..len)
+                .map(|i| {
+                    let pos = i - start;
+                    if pos < 0 || pos >= size {
+                        return ONE;
+                    }
+                    // where inside its emulated pixel this row/column is, 0..size
+                    let within = pos * src % size;
+                    let mut factor = ONE;
+                    if lines && scanlines && within * 3 >= size * 2 {
+                        factor = factor * SCANLINE / ONE;
+                    }
+                    if lines && has(Filter::Grid) && within < src {
+                        factor = factor * GRID / ONE;
+                    }
+                    if has(Filter::Vignette) {
+                        // -1..1 across the picture, squared
+                        let t = (2 * pos + 1 - size) as i64;
+                        let t2 = (t * t * VIGNETTE as i64 / (size as i64 * size as i64)) as u32;
+                        factor = factor * (ONE - t2) / ONE;
+                    }
+                    factor
+                })
+                .collect::<Vec<_>>()
+        };
+        Some(Self {
+            rows: axis(height, dst.y, dst.height, src_h, has(Filter::Scanlines)),
+            columns: axis(width, dst.x, dst.width, src_w, false),
+            width,
+            height,
+            dst,
+        })
+    }
+}
+
+// `color` at `factor` / `ONE` brightness
+#[inline]
+pub fn shade(color: u32, factor: u32) -> u32 {
+    let rb = (((color & 0xFF00FF) * factor) >> 8) & 0xFF00FF;
+    let g = (((color & 0x00FF00) * factor) >> 8) & 0x00FF00;
+    rb | g
+}
+
+// Adds a bit of every pixel's 3x3 neighbourhood on top of it
+pub fn bloom(colors: &[u32], width: usize, height: usize) -> Vec<u32> {
+    let channel = |c: u32, shift: u32| (c >> shift) & 0xFF;
+    (0..width * height)
+        .map(|i| {
+            let (x, y) = (i % width, i / width);
+            let mut sum = [0u32; 3];
+            for ny in y.saturating_sub(1)..(y + 2).min(height) {
+                for nx in x.saturating_sub(1)..(x + 2).min(width) {
+                    let c = colors[ny * width + nx];
+                    sum[0] += channel(c, 16);
+                    sum[1] += channel(c, 8);
+                    sum[2] += channel(c, 0);
+                }
+            }
+            let c = colors[i];
+            // a quarter of the neighbourhood average
+            let glow = |s: u32, shift| (channel(c, shift) + s / 36).min(0xFF) << shift;
+            glow(sum[0], 16) | glow(sum[1], 8) | glow(sum[2], 0)
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_filters() {
+        assert_eq!(parse_list("scanlines,vignette"), Ok(vec![Filter::Scanlines, Filter::Vignette]));
+        assert_eq!(parse_list("none"), Ok(vec![]));
+        assert!(parse_list("scanlines,sepia").is_err());
+    }
+
+    #[test]
+    fn scanlines_darken_bottom_of_each_row() {
+        let dst = Rect { x: 0, y: 2, width: 64 * 3, height: 32 * 3 };
+        let masks = Masks::new(&[Filter::Scanlines], dst.width, 100, dst, 64, 32).unwrap();
+        assert_eq!(masks.rows[..8], [ONE, ONE, ONE, ONE, SCANLINE, ONE, ONE, SCANLINE]);
+        assert!(masks.columns.iter().all(|c| *c == ONE));
+        assert!(Masks::new(&[Filter::Bloom], 100, 100, dst, 64, 32).is_none());
+    }
+
+    #[test]
+    fn vignette_is_symmetric() {
+        let dst = Rect { x: 0, y: 0, width: 128, height: 64 };
+        let masks = Masks::new(&[Filter::Vignette], 128, 64, dst, 64, 32).unwrap();
+        assert_eq!(masks.columns[0], masks.columns[127]);
+        assert!(masks.columns[0] < masks.columns[64]);
+        assert!(masks.columns[64] > ONE - 2);
+    }
+
+    #[test]
+    fn shade_and_bloom() {
+        assert_eq!(shade(0xFF8040, ONE), 0xFF8040);
+        assert_eq!(shade(0xFF8040, 128), 0x7F4020);
+        let mut colors = vec![0u32; 9];
+        colors[4] = 0xFFFFFF;
+        let glow = bloom(&colors, 3, 3);
+        assert_eq!(glow[4], 0xFFFFFF);
+        assert_eq!(glow[0], 0x070707);
+    }
+}
@@ -0,0 +1,44 @@
+use super::chip8::Chip8;
+use super::display::Display;
+use super::options::Options;
+use super::record::{self, Recorder};
+use super::{font, palette, TICK_MS};
+use scratchway::log;
+
+// Runs the rom without a window for `frames` video frames and records
+// every one of them. Emulated time goes by at the same rate as in the
+// window, just as fast as the machine manages.
+pub fn run(opts: &Options, path: &str, frames: u64) -> std::io::Result<()> {
+    let glyphs = match opts.font_file.as_deref() {
+        Some(path) => font::load(path)?,
+        None => *opts.font.glyphs(),
+    };
+    let mut chip8 = Chip8::new();
+    chip8.set_font(&glyphs, opts.font_base).map_err(std::io::Error::other)?;
+    chip8.load(&opts.rom_path)?;
+    chip8.set_backend(opts.backend)?;
+    let palette = match opts.palette_file.as_deref() {
+        Some(path) => palette::load(path)?,
+        None => opts.colors.unwrap_or(opts.theme.palette()),
+    };
+    let mut display = Display::new(opts.persistence, opts.decay);
+    let format = record::Format::from_path(path).map_err(std::io::Error::other)?;
+    let mut recorder = Recorder::new(std::io::BufWriter::new(std::fs::File::create(path)?), format)?;
+
+    // timer ticks up to the start of `frame`
+    let ticks = |frame: u64| frame * 1000 / (record::FPS * TICK_MS);
+    // emulated time, for fading
+    let start = std::time::Instant::now();
+    let frame_time = std::time::Duration::from_secs(1) / record::FPS as u32;
+    for frame in 0..frames {
+        if let Err(e) = chip8.run(ticks(frame + 1) - ticks(frame)) {
+            log!(ERR, "Emulation halted after {} frames: {}", frame, e);
+            break;
+        }
+        display.compose(&chip8.framebuffer, &palette, &mut chip8.dirty, start + frame_time * frame as u32);
+        chip8.dirty.clear();
+        recorder.frame(&display.colors, 1)?;
+    }
+    recorder.finish()?;
+    Ok(())
+}
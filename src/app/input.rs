@@ -23,6 +23,25 @@ impl App {
                         wl_keyboard.release(conn.writer());
                     }
                 }
+                // only for the on-screen keypad, but that's decided later
+                if capabilities & wl_seat::CAPABILITY_POINTER > 0 {
+                    if self.wl_pointer.is_none() {
+                        let wl_pointer = wl_seat.get_pointer(conn.writer());
+                        self.add_callback(wl_pointer.id(), Self::on_wlpointer);
+                        self.wl_pointer = Some(wl_pointer);
+                    }
+                } else if let Some(wl_pointer) = self.wl_pointer.take() {
+                    wl_pointer.release(conn.writer());
+                }
+                if capabilities & wl_seat::CAPABILITY_TOUCH > 0 {
+                    if self.wl_touch.is_none() {
+                        let wl_touch = wl_seat.get_touch(conn.writer());
+                        self.add_callback(wl_touch.id(), Self::on_wltouch);
+                        self.wl_touch = Some(wl_touch);
+                    }
+                } else if let Some(wl_touch) = self.wl_touch.take() {
+                    wl_touch.release(conn.writer());
+                }
             },
             wl_seat::Event::Name { .. } => {},
         }
@@ -37,60 +56,84 @@ impl App {
         match wl_keyboard.parse_event(conn.reader(), event) {
             wl_keyboard::Event::Enter { serial, surface, keys } => {
                 // self.focused_surface = Some(surface);
+                // keys already down when focus came in, a wl_array of u32
+                // scancodes
+                let held: Vec<u32> = keys
+                    .chunks_exact(4)
+                    .map(|k| u32::from_ne_bytes([k[0], k[1], k[2], k[3]]))
+                    .collect();
+                self.release_keys(conn);
+                for key in held {
+                    let keysym = self.keymap.as_ref().and_then(|k| k.keysym(key));
+                    // one shot actions only fire on a fresh press
+                    if let Some(action @ (bindings::Action::Key(_) | bindings::Action::FastForward)) =
+                        self.bindings.get(key, keysym)
+                    {
+                        self.run_action(conn, action, wl_keyboard::KeyState::Pressed);
+                    }
+                }
+                self.set_focus(conn, true);
             },
             wl_keyboard::Event::Key { serial, time, key, state } => {
-                let pressed = state != wl_keyboard::KeyState::Released;
-                let keys = &mut self.chip8.keys;
-                match key {
-                    // Escape
-                    1 => self.exit = true,
-                    // 1 
-                    2 => keys[0x1] = pressed,
-                    // 2
-                    3 => keys[0x2] = pressed,
-                    // 3
-                    4 => keys[0x3] = pressed,
-                    // 4
-                    5 => keys[0xC] = pressed,
-
-                    // Q
-                    16 => keys[0x4] = pressed,
-                    // W
-                    17 => keys[0x5] = pressed,
-                    // E
-                    18 => keys[0x6] = pressed,
-                    // R
-                    19 => keys[0xD] = pressed,
-
-
-                    // A
-                    30 => keys[0x7] = pressed,
-                    // S
-                    31 => keys[0x8] = pressed,
-                    // D
-                    32 => keys[0x9] = pressed,
-                    // F
-                    33 => keys[0xE] = pressed,
-
-                    // Z
-                    44 => keys[0xA] = pressed,
-                    // X
-                    45 => keys[0x0] = pressed,
-                    // E
-                    46 => keys[0xB] = pressed,
-                    // R
-                    47 => keys[0xF] = pressed,
-                    _ => {}
+                if state == wl_keyboard::KeyState::Pressed {
+                    self.key_serial = serial;
+                }
+                let keysym = self.keymap.as_ref().and_then(|k| k.keysym(key));
+                if let Some(action) = self.bindings.get(key, keysym) {
+                    self.run_action(conn, action, state);
                 }
             },
             wl_keyboard::Event::Leave { serial, surface } => {
+                // their releases go to whoever has focus now
+                self.release_keys(conn);
+                self.set_focus(conn, false);
+            },
+            wl_keyboard::Event::Keymap { format, fd, size } => {
+                if format != wl_keyboard::KeymapFormat::XkbV1 as u32 {
+                    self.keymap = None;
+                    return;
+                }
+                // keysym bindings just don't match without one
+                match keymap::Keymap::read(fd, size) {
+                    Ok(keymap) => self.keymap = Some(keymap),
+                    Err(e) => log!(WARNING, "Keymap - {}", e),
+                }
             },
-            wl_keyboard::Event::Keymap { format, fd, size } => {},
             wl_keyboard::Event::Modifiers { serial, mods_depressed, mods_latched, mods_locked, group } => {},
             wl_keyboard::Event::RepeatInfo { rate, delay } => {},
             _ => {}
         }
     }
 
-    pub(crate) fn pass_on_mouse(&mut self, conn: &Connection, event: wl_keyboard::Event) {}
+    fn run_action(&mut self, conn: &Connection, action: bindings::Action, state: wl_keyboard::KeyState) {
+        use bindings::Action;
+        let pressed = state != wl_keyboard::KeyState::Released;
+        // a fresh press, not a repeat
+        let down = state == wl_keyboard::KeyState::Pressed;
+        match action {
+            // repeats don't count as another key holding it
+            Action::Key(key) if down || !pressed => self.set_key(key, down),
+            Action::Quit if down => self.exit = true,
+            Action::Pause if down => self.toggle_pause(conn),
+            Action::Advance if down => self.advance_frame(),
+            Action::Reset if down => self.reset(conn),
+            Action::Slower if pressed => self.change_speed(conn, false),
+            Action::Faster if pressed => self.change_speed(conn, true),
+            Action::FastForward => self.set_fast_forward(conn, pressed),
+            Action::Stats if down => self.toggle_stats(),
+            Action::Palette if down => self.cycle_palette(),
+            Action::Maximize if down => self.toggle_maximized(conn),
+            Action::Fullscreen if down => self.toggle_fullscreen(conn),
+            Action::Screenshot if down => self.save_screenshot(),
+            Action::CopyScreenshot if down => self.copy_screenshot(conn),
+            Action::Record if down => self.toggle_recording(),
+            _ => {}
+        }
+    }
+
+    fn release_keys(&mut self, conn: &Connection) {
+        self.keyboard_keys = [0; 16];
+        self.sync_keys();
+        self.set_fast_forward(conn, false);
+    }
 }
@@ -0,0 +1,352 @@
+// x86-64 recompiler for straight line CHIP-8 code.
+//
+// A block starts at some PC and runs until the first branch (JP, SE, SNE),
+// which it compiles as its last instruction, or until the first instruction
+// it can't compile. Anything touching memory, the stack, timers, keys, the
+// screen or RND is left to the interpreter, so blocks only ever read and
+// write `registers`, `index` and `program_counter` through the `*mut Chip8`
+// they're called with (System V: rdi).
+use std::mem::offset_of;
+use std::ops::Range;
+
+use super::chip8::{Chip8, MEMORY_SIZE};
+use crate::shared::exec::ExecMemory;
+
+pub(crate) type BlockFn = unsafe extern "sysv64" fn(*mut Chip8);
+
+const ARENA_SIZE: usize = 256 * 1024;
+const MAX_BLOCK_LEN: usize = 64;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Block {
+    pub code: BlockFn,
+    /// Instructions in the block, they all run on every call
+    pub len: u64,
+    start: usize,
+    end: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Slot {
+    Unknown,
+    Interpret,
+    Compiled(Block),
+}
+
+#[derive(Debug)]
+pub(crate) struct Jit {
+    arena: ExecMemory,
+    used: usize,
+    slots: Vec<Slot>,
+    live: Vec<usize>,
+}
+
+impl Jit {
+    pub fn new() -> std::io::Result<Self> {
+        Ok(Self {
+            arena: ExecMemory::new(ARENA_SIZE)?,
+            used: 0,
+            slots: vec![Slot::Unknown; MEMORY_SIZE],
+            live: Vec::new(),
+        })
+    }
+
+    // Block starting at `pc`, compiled on first use. `None` when the
+    // instruction at `pc` has to go through the interpreter.
+    pub fn block(&mut self, memory: &[u8; MEMORY_SIZE], pc: usize) -> Option<Block> {
+        let slot = *self.slots.get(pc)?;
+        match slot {
+            Slot::Compiled(block) => Some(block),
+            Slot::Interpret => None,
+            Slot::Unknown => {
+                let slot = self.compile(memory, pc);
+                self.slots[pc] = slot;
+                match slot {
+                    Slot::Compiled(block) => Some(block),
+                    _ => None,
+                }
+            }
+        }
+    }
+
+    // Drops every block overlapping written memory, just like the
+    // decode cache the instruction starting one byte earlier counts too.
+    pub fn invalidate(&mut self, range: Range<usize>) {
+        let start = range.start.saturating_sub(1);
+        let end = range.end.min(MEMORY_SIZE);
+        let slots = &mut self.slots;
+        self.live.retain(|&addr| {
+            let Slot::Compiled(block) = slots[addr] else {
+                return false;
+            };
+            if block.start < end && start < block.end {
+                slots[addr] = Slot::Unknown;
+                return false;
+            }
+            true
+        });
+        if start < end {
+            slots[start..end].fill(Slot::Unknown);
+        }
+    }
+
+    fn compile(&mut self, memory: &[u8; MEMORY_SIZE], pc: usize) -> Slot {
+        let mut asm = Asm::default();
+        let mut addr = pc;
+        let mut len = 0;
+        loop {
+            if addr + 1 >= MEMORY_SIZE || len == MAX_BLOCK_LEN {
+                asm.set_pc(addr);
+                break;
+            }
+            let opcode = u16::from_be_bytes([memory[addr], memory[addr + 1]]);
+            match asm.emit(opcode, addr) {
+                Emitted::Straight => {}
+                Emitted::Branch => {
+                    addr += 2;
+                    len += 1;
+                    break;
+                }
+                Emitted::Unsupported => {
+                    asm.set_pc(addr);
+                    break;
+                }
+            }
+            addr += 2;
+            len += 1;
+        }
+        if len == 0 {
+            return Slot::Interpret;
+        }
+        asm.ret();
+
+        if self.used + asm.code.len() > self.arena.size() {
+            self.flush();
+        }
+        if self.arena.write(self.used, &asm.code).is_err() {
+            return Slot::Interpret;
+        }
+        let code = unsafe {
+            let ptr = self.arena.as_ptr().add(self.used);
+            std::mem::transmute::<*const u8, BlockFn>(ptr)
+        };
+        self.used += asm.code.len();
+        self.live.push(pc);
+
+        Slot::Compiled(Block {
+            code,
+            len: len as u64,
+            start: pc,
+            end: addr,
+        })
+    }
+
+    // Out of space, start over
+    fn flush(&mut self) {
+        self.slots.fill(Slot::Unknown);
+        self.live.clear();
+        self.used = 0;
+    }
+}
+
+enum Emitted {
+    Straight,
+    Branch,
+    Unsupported,
+}
+
+const fn v(x: usize) -> i32 {
+    (offset_of!(Chip8, registers) + x) as i32
+}
+const VF: i32 = v(0xF);
+const INDEX: i32 = offset_of!(Chip8, index) as i32;
+const PC: i32 = offset_of!(Chip8, program_counter) as i32;
+
+// al/cl/dl as scratch, everything else addressed as [rdi + disp32]
+#[derive(Default)]
+struct Asm {
+    code: Vec<u8>,
+}
+
+impl Asm {
+    fn emit(&mut self, opcode: u16, addr: usize) -> Emitted {
+        let x = ((opcode & 0x0F00) >> 8) as usize;
+        let y = ((opcode & 0x00F0) >> 4) as usize;
+        let n = opcode & 0x000F;
+        let kk = (opcode & 0x00FF) as u8;
+        let nnn = (opcode & 0x0FFF) as i32;
+        let next = (addr + 2) as i32;
+        let skip = (addr + 4) as i32;
+
+        match (opcode & 0xF000) >> 12 {
+            0x1 => {
+                self.set_pc(nnn as usize);
+                return Emitted::Branch;
+            }
+            0x3 | 0x4 => {
+                self.cmp_imm(v(x), kk);
+                self.skip_if(opcode & 0xF000 == 0x3000, next, skip);
+                return Emitted::Branch;
+            }
+            0x5 | 0x9 if n == 0 => {
+                self.load_al(v(x));
+                self.cmp_al(v(y));
+                self.skip_if(opcode & 0xF000 == 0x5000, next, skip);
+                return Emitted::Branch;
+            }
+            0x6 => self.mov_imm(v(x), kk),
+            0x7 => self.add_imm(v(x), kk),
+            0x8 => match n {
+                0x0 => {
+                    self.load_al(v(y));
+                    self.store_al(v(x));
+                }
+                0x1..=0x3 => {
+                    self.load_al(v(x));
+                    self.load_cl(v(y));
+                    self.alu_al_cl(match n {
+                        0x1 => 0x08, // or
+                        0x2 => 0x20, // and
+                        _ => 0x30,   // xor
+                    });
+                    self.store_al(v(x));
+                }
+                // result first, the flag wins
+                0x4 => {
+                    self.load_al(v(x));
+                    self.load_cl(v(y));
+                    self.alu_al_cl(0x00); // add
+                    self.setcc_dl(0x92); // setc
+                    self.store_al(v(x));
+                    self.store_dl(VF);
+                }
+                // flag first, the result wins
+                0x5 | 0x7 => {
+                    let (a, b) = if n == 0x5 { (x, y) } else { (y, x) };
+                    self.load_al(v(a));
+                    self.load_cl(v(b));
+                    self.alu_al_cl(0x28); // sub
+                    self.setcc_dl(0x93); // setnc
+                    self.store_dl(VF);
+                    self.store_al(v(x));
+                }
+                // flag first, then shift whatever Vx is by now
+                0x6 | 0xE => {
+                    self.load_al(v(x));
+                    self.bytes(&[0x88, 0xC2]); // mov dl, al
+                    if n == 0x6 {
+                        self.bytes(&[0x80, 0xE2, 0x01]); // and dl, 1
+                    } else {
+                        self.bytes(&[0xC0, 0xEA, 0x07]); // shr dl, 7
+                    }
+                    self.store_dl(VF);
+                    self.load_al(v(x));
+                    if n == 0x6 {
+                        self.bytes(&[0xD0, 0xE8]); // shr al, 1
+                    } else {
+                        self.bytes(&[0xD0, 0xE0]); // shl al, 1
+                    }
+                    self.store_al(v(x));
+                }
+                _ => return Emitted::Unsupported,
+            },
+            0xA => self.mov_qword_imm(INDEX, nnn),
+            0xF if kk == 0x1E => {
+                self.bytes(&[0x0F, 0xB6, 0x87]); // movzx eax, byte [rdi + Vx]
+                self.disp(v(x));
+                self.bytes(&[0x48, 0x01, 0x87]); // add [rdi + index], rax
+                self.disp(INDEX);
+                self.bytes(&[0x48, 0x8B, 0x87]); // mov rax, [rdi + index]
+                self.disp(INDEX);
+                self.bytes(&[0x48, 0x3D]); // cmp rax, 0xF00
+                self.disp(0x0F00);
+                self.setcc_dl(0x97); // seta
+                self.store_dl(VF);
+            }
+            _ => return Emitted::Unsupported,
+        }
+        Emitted::Straight
+    }
+
+    // PC = next, and PC = skip when the last compare was equal (or not)
+    fn skip_if(&mut self, equal: bool, next: i32, skip: i32) {
+        self.mov_qword_imm(PC, next);
+        let jcc = if equal { 0x75 } else { 0x74 }; // jne / je over the next mov
+        self.bytes(&[jcc, 11]);
+        self.mov_qword_imm(PC, skip);
+    }
+
+    fn set_pc(&mut self, pc: usize) {
+        self.mov_qword_imm(PC, pc as i32);
+    }
+
+    fn load_al(&mut self, disp: i32) {
+        self.bytes(&[0x8A, 0x87]);
+        self.disp(disp);
+    }
+
+    fn load_cl(&mut self, disp: i32) {
+        self.bytes(&[0x8A, 0x8F]);
+        self.disp(disp);
+    }
+
+    fn store_al(&mut self, disp: i32) {
+        self.bytes(&[0x88, 0x87]);
+        self.disp(disp);
+    }
+
+    fn store_dl(&mut self, disp: i32) {
+        self.bytes(&[0x88, 0x97]);
+        self.disp(disp);
+    }
+
+    fn cmp_al(&mut self, disp: i32) {
+        self.bytes(&[0x3A, 0x87]);
+        self.disp(disp);
+    }
+
+    fn mov_imm(&mut self, disp: i32, imm: u8) {
+        self.bytes(&[0xC6, 0x87]);
+        self.disp(disp);
+        self.bytes(&[imm]);
+    }
+
+    fn add_imm(&mut self, disp: i32, imm: u8) {
+        self.bytes(&[0x80, 0x87]);
+        self.disp(disp);
+        self.bytes(&[imm]);
+    }
+
+    fn cmp_imm(&mut self, disp: i32, imm: u8) {
+        self.bytes(&[0x80, 0xBF]);
+        self.disp(disp);
+        self.bytes(&[imm]);
+    }
+
+    fn mov_qword_imm(&mut self, disp: i32, imm: i32) {
+        self.bytes(&[0x48, 0xC7, 0x87]);
+        self.disp(disp);
+        self.disp(imm);
+    }
+
+    // <op> al, cl
+    fn alu_al_cl(&mut self, op: u8) {
+        self.bytes(&[op, 0xC8]);
+    }
+
+    fn setcc_dl(&mut self, cc: u8) {
+        self.bytes(&[0x0F, cc, 0xC2]);
+    }
+
+    fn ret(&mut self) {
+        self.bytes(&[0xC3]);
+    }
+
+    fn disp(&mut self, disp: i32) {
+        self.bytes(&disp.to_le_bytes());
+    }
+
+    fn bytes(&mut self, bytes: &[u8]) {
+        self.code.extend_from_slice(bytes);
+    }
+}
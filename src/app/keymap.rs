@@ -0,0 +1,178 @@
+// Just enough of the xkb text format to know which keysym sits on a key:
+// the keycodes section names every keycode, the symbols section puts
+// keysyms on those names. Only the first level of the first group is kept,
+// modifiers aren't tracked so bindings see `q`, never `Q`.
+use std::collections::HashMap;
+use std::os::fd::{FromRawFd, IntoRawFd, OwnedFd};
+use std::os::unix::fs::FileExt;
+
+/// xkb keycodes are evdev scancodes shifted by this
+pub const EVDEV_OFFSET: u32 = 8;
+
+#[derive(Debug, Default)]
+pub(crate) struct Keymap {
+    /// Keysym names by evdev scancode
+    keysyms: HashMap<u32, String>,
+}
+
+impl Keymap {
+    // `size` includes the terminating nul
+    pub fn read(fd: impl IntoRawFd, size: u32) -> std::io::Result<Self> {
+        let file = std::fs::File::from(unsafe { OwnedFd::from_raw_fd(fd.into_raw_fd()) });
+        let mut data = vec![0; size as usize];
+        // the compositor may hand the same fd to every client, reading
+        // at an offset leaves the shared file position alone
+        file.read_exact_at(&mut data, 0)?;
+        let end = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+        Self::parse(&String::from_utf8_lossy(&data[..end]))
+            .ok_or_else(|| std::io::Error::new(std::io::ErrorKind::InvalidData, "Not an xkb keymap"))
+    }
+
+    pub fn parse(text: &str) -> Option<Self> {
+        let text: String = text
+            .lines()
+            .map(|l| l.split_once("//").map_or(l, |(l, _)| l))
+            .collect::<Vec<_>>()
+            .join("\n");
+
+        let mut codes = HashMap::new();
+        let mut aliases = HashMap::new();
+        for statement in section(&text, "xkb_keycodes")?.split(';') {
+            let Some((name, value)) = statement.split_once('=') else {
+                continue;
+            };
+            let name = name.trim();
+            if let Some(alias) = name.strip_prefix("alias") {
+                aliases.insert(key_name(alias)?, key_name(value)?);
+            } else if name.starts_with('<') {
+                let Ok(code) = value.trim().parse::<u32>() else {
+                    continue;
+                };
+                codes.insert(key_name(name)?, code);
+            }
+        }
+
+        let mut keysyms = HashMap::new();
+        let mut rest = section(&text, "xkb_symbols")?;
+        while let Some(start) = rest.find("key ") {
+            rest = &rest[start + 4..];
+            let Some(body_start) = rest.find('{') else {
+                break;
+            };
+            let name = rest[..body_start].trim();
+            let body = block(&rest[body_start..])?;
+            rest = &rest[body_start + body.len()..];
+
+            let Some(name) = key_name(name) else {
+                continue;
+            };
+            let name = aliases.get(name).copied().unwrap_or(name);
+            let (Some(code), Some(keysym)) = (codes.get(name), first_keysym(body)) else {
+                continue;
+            };
+            if let Some(scancode) = code.checked_sub(EVDEV_OFFSET) {
+                keysyms.insert(scancode, keysym.to_string());
+            }
+        }
+        Some(Self { keysyms })
+    }
+
+    pub fn keysym(&self, scancode: u32) -> Option<&str> {
+        self.keysyms.get(&scancode).map(|k| k.as_str())
+    }
+}
+
+// Inside of the braces following `name`
+fn section<'a>(text: &'a str, name: &str) -> Option<&'a str> {
+    let start = text.find(name)?;
+    let open = start + text[start..].find('{')?;
+    let body = block(&text[open..])?;
+    Some(&body[1..body.len() - 1])
+}
+
+// `text` starting at a `{` up to and including its closing `}`
+fn block(text: &str) -> Option<&str> {
+    let mut depth = 0;
+    for (i, c) in text.char_indices() {
+        match c {
+            '{' => depth += 1,
+            '}' => {
+                depth -= 1;
+                if depth == 0 {
+                    return Some(&text[..=i]);
+                }
+            }
+            _ => {}
+        }
+    }
+    None
+}
+
+// `AE01` out of ` <AE01> `
+fn key_name(s: &str) -> Option<&str> {
+    s.trim().strip_prefix('<')?.strip_suffix('>')
+}
+
+// `{ [ q, Q ] }` or `{ type= "ALPHABETIC", symbols[Group1]= [ q, Q ] }`
+fn first_keysym(body: &str) -> Option<&str> {
+    let levels = match body.find("symbols[") {
+        Some(i) => {
+            let after = &body[i..];
+            &after[after.find(']')? + 1..]
+        }
+        None => body,
+    };
+    let levels = &levels[levels.find('[')? + 1..];
+    let levels = &levels[..levels.find(']')?];
+    let keysym = levels.split(',').next()?.trim();
+    (!keysym.is_empty() && keysym != "NoSymbol").then_some(keysym)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KEYMAP: &str = r#"xkb_keymap {
+xkb_keycodes "evdev+aliases(azerty)" {
+	minimum = 8;
+	maximum = 255;
+	<ESC>                = 9;
+	<AE01>               = 10;
+	<AD01>               = 24;
+	<AC01>               = 38;
+	indicator 1 = "Caps Lock";
+	alias <LatQ>         = <AD01>;
+};
+xkb_types "complete" {
+	virtual_modifiers NumLock;
+	type "ONE_LEVEL" {
+		modifiers= none;
+	};
+};
+xkb_symbols "pc+fr+inet(evdev)" {
+	name[group1]="French";
+	key <ESC>                {	[          Escape ] };
+	key <AE01>               {	[       ampersand,               1,     onesuperior ] };
+	key <LatQ>               {
+		type= "FOUR_LEVEL_SEMIALPHABETIC",
+		symbols[Group1]= [               a,               A,      adiaeresis ]
+	};
+	// a comment { with a brace
+	key <AC01>               {	[               q,               Q ] };
+	modifier_map Control { <LCTL> };
+};
+};
+"#;
+
+    #[test]
+    fn keysyms_by_scancode() {
+        let keymap = Keymap::parse(KEYMAP).unwrap();
+        assert_eq!(keymap.keysym(1), Some("Escape"));
+        assert_eq!(keymap.keysym(2), Some("ampersand"));
+        // through the alias and past `symbols[Group1]`
+        assert_eq!(keymap.keysym(16), Some("a"));
+        assert_eq!(keymap.keysym(30), Some("q"));
+        assert_eq!(keymap.keysym(31), None);
+        assert!(Keymap::parse("not a keymap").is_none());
+    }
+}
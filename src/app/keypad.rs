@@ -0,0 +1,220 @@
+// 4x4 hex keypad drawn next to the picture for mice and touchscreens. The
+// mouse and every finger each hold at most one key, a key stays down as
+// long as any of them holds it.
+use super::osd::{self, GLYPH_H, GLYPH_W};
+use super::palette::Palette;
+use super::render::{Canvas, Rect};
+use super::{chip8, filter};
+
+/// Same arrangement as the COSMAC VIP keypad
+pub const LAYOUT: [[u8; 4]; 4] = [
+    [0x1, 0x2, 0x3, 0xC],
+    [0x4, 0x5, 0x6, 0xD],
+    [0x7, 0x8, 0x9, 0xE],
+    [0xA, 0x0, 0xB, 0xF],
+];
+/// Released keys at this brightness of the foreground
+const DIM: u32 = 64;
+
+#[derive(Debug, Default)]
+pub(crate) struct Keypad {
+    /// Where it went on the last frame
+    pub panel: Rect,
+    /// Pointer position and the key under it while the button is held
+    pointer: (i32, i32),
+    button: bool,
+    clicked: Option<u8>,
+    /// Touch point ids and the key under each
+    touches: Vec<(i32, Option<u8>)>,
+    /// Key states the panel was last drawn with
+    drawn: [bool; 16],
+}
+
+impl Keypad {
+    // Keys down because of the mouse or touches
+    pub fn held(&self) -> [bool; 16] {
+        let mut held = [false; 16];
+        for key in self.clicked.iter().chain(self.touches.iter().filter_map(|(_, k)| k.as_ref())) {
+            held[*key as usize] = true;
+        }
+        held
+    }
+
+    pub fn pointer_motion(&mut self, x: i32, y: i32) {
+        self.pointer = (x, y);
+        // dragging off a key lets go of it, onto another presses that one
+        if self.button {
+            self.clicked = key_at(self.panel, x, y);
+        }
+    }
+
+    pub fn pointer_button(&mut self, pressed: bool) {
+        self.button = pressed;
+        self.clicked = match pressed {
+            true => key_at(self.panel, self.pointer.0, self.pointer.1),
+            false => None,
+        };
+    }
+
+    pub fn pointer_leave(&mut self) {
+        self.button = false;
+        self.clicked = None;
+    }
+
+    pub fn touch_down(&mut self, id: i32, x: i32, y: i32) {
+        self.touches.retain(|(t, _)| *t != id);
+        self.touches.push((id, key_at(self.panel, x, y)));
+    }
+
+    pub fn touch_motion(&mut self, id: i32, x: i32, y: i32) {
+        if let Some(touch) = self.touches.iter_mut().find(|(t, _)| *t == id) {
+            touch.1 = key_at(self.panel, x, y);
+        }
+    }
+
+    pub fn touch_up(&mut self, id: i32) {
+        self.touches.retain(|(t, _)| *t != id);
+    }
+
+    // The compositor took the touches over for a gesture
+    pub fn touch_cancel(&mut self) {
+        self.touches.clear();
+    }
+
+    // Keys that look different from the last frame, `keys` is what the
+    // panel shows from now on
+    pub fn damage(&mut self, keys: &[bool; 16]) -> Vec<Rect> {
+        let damage = (0..16u8)
+            .filter(|k| self.drawn[*k as usize] != keys[*k as usize])
+            .filter_map(|k| key_rect(self.panel, k))
+            .collect();
+        self.drawn = *keys;
+        damage
+    }
+
+    // Repaints whatever part of the panel is inside `clip`
+    pub fn draw(&self, canvas: &mut Canvas<'_>, palette: &Palette, clip: Rect) {
+        let area = self.panel.intersect(&clip);
+        if area.is_empty() {
+            return;
+        }
+        fill(canvas, area, palette[0]);
+        let scale = (self.panel.height / 4 / (GLYPH_H * 3)).max(1);
+        for key in 0..16u8 {
+            let Some(rect) = key_rect(self.panel, key) else {
+                continue;
+            };
+            let (button, label) = match self.drawn[key as usize] {
+                true => (palette[1], palette[0]),
+                false => (filter::shade(palette[1], DIM), palette[1]),
+            };
+            fill(canvas, rect.intersect(&area), button);
+            let text = format!("{:X}", key);
+            let x = rect.x + (rect.width - GLYPH_W * scale) / 2;
+            let y = rect.y + (rect.height - GLYPH_H * scale) / 2;
+            osd::draw_text(canvas, x, y, scale, &text, label, rect.intersect(&area));
+        }
+    }
+}
+
+// Picture area and panel splitting `area`. Wide windows get the panel on
+// the right, tall ones underneath.
+pub fn split(area: Rect) -> (Rect, Rect) {
+    let (w, h) = (chip8::WIDTH as i32, chip8::HEIGHT as i32);
+    let Rect { x, y, width, height } = area;
+    // whatever leaves the picture bigger
+    if width * h > height * w {
+        let side = height.min(width / 3);
+        let picture = Rect { x, y, width: width - side, height };
+        let panel = Rect { x: x + width - side, y: y + (height - side) / 2, width: side, height: side };
+        (picture, panel)
+    } else {
+        let side = width.min(height / 2);
+        let picture = Rect { x, y, width, height: height - side };
+        let panel = Rect { x: x + (width - side) / 2, y: y + height - side, width: side, height: side };
+        (picture, panel)
+    }
+}
+
+// Button for `key`, with a gap around it
+fn key_rect(panel: Rect, key: u8) -> Option<Rect> {
+    let (row, column) = LAYOUT
+        .iter()
+        .enumerate()
+        .find_map(|(r, row)| row.iter().position(|k| *k == key).map(|c| (r as i32, c as i32)))?;
+    let (x0, x1) = (panel.x + panel.width * column / 4, panel.x + panel.width * (column + 1) / 4);
+    let (y0, y1) = (panel.y + panel.height * row / 4, panel.y + panel.height * (row + 1) / 4);
+    let gap = ((x1 - x0) / 12).max(1);
+    let rect = Rect { x: x0 + gap, y: y0 + gap, width: x1 - x0 - 2 * gap, height: y1 - y0 - 2 * gap };
+    Some(rect).filter(|r| !r.is_empty())
+}
+
+// Gaps count as the nearest key so fingers don't fall through
+pub fn key_at(panel: Rect, x: i32, y: i32) -> Option<u8> {
+    if !panel.contains(x, y) {
+        return None;
+    }
+    let column = (x - panel.x) * 4 / panel.width;
+    let row = (y - panel.y) * 4 / panel.height;
+    Some(LAYOUT[row as usize][column as usize])
+}
+
+fn fill(canvas: &mut Canvas<'_>, rect: Rect, color: u32) {
+    let rect = rect.intersect(&Rect { x: 0, y: 0, width: canvas.width as i32, height: canvas.height as i32 });
+    for y in rect.y..rect.y + rect.height {
+        let start = y as usize * canvas.width + rect.x as usize;
+        canvas.pixels[start..start + rect.width as usize].fill(color);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_gives_the_picture_the_long_side() {
+        let (picture, panel) = split(Rect { x: 0, y: 0, width: 900, height: 300 });
+        assert_eq!(panel, Rect { x: 600, y: 0, width: 300, height: 300 });
+        assert_eq!(picture, Rect { x: 0, y: 0, width: 600, height: 300 });
+        let (picture, panel) = split(Rect { x: 0, y: 20, width: 400, height: 600 });
+        assert_eq!(panel, Rect { x: 50, y: 320, width: 300, height: 300 });
+        assert_eq!(picture, Rect { x: 0, y: 20, width: 400, height: 300 });
+    }
+
+    #[test]
+    fn touches_and_clicks_hold_keys() {
+        let mut keypad = Keypad { panel: Rect { x: 100, y: 0, width: 40, height: 40 }, ..Default::default() };
+        assert_eq!(key_at(keypad.panel, 100, 0), Some(0x1));
+        assert_eq!(key_at(keypad.panel, 139, 39), Some(0xF));
+        assert_eq!(key_at(keypad.panel, 99, 0), None);
+
+        keypad.touch_down(3, 105, 5);
+        keypad.touch_down(7, 115, 35);
+        keypad.pointer_motion(105, 5);
+        keypad.pointer_button(true);
+        let held = keypad.held();
+        assert!(held[0x1] && held[0x0]);
+        assert_eq!(held.iter().filter(|h| **h).count(), 2);
+
+        // the click still holds 1 after the finger lets go
+        keypad.touch_up(3);
+        assert!(keypad.held()[0x1]);
+        keypad.pointer_button(false);
+        assert!(!keypad.held()[0x1]);
+        // sliding a finger over
+        keypad.touch_motion(7, 135, 35);
+        assert!(keypad.held()[0xF] && !keypad.held()[0x0]);
+        keypad.touch_cancel();
+        assert_eq!(keypad.held(), [false; 16]);
+    }
+
+    #[test]
+    fn damage_follows_key_changes() {
+        let mut keypad = Keypad { panel: Rect { x: 0, y: 0, width: 48, height: 48 }, ..Default::default() };
+        let mut keys = [false; 16];
+        assert!(keypad.damage(&keys).is_empty());
+        keys[0x5] = true;
+        assert_eq!(keypad.damage(&keys), [Rect { x: 13, y: 13, width: 10, height: 10 }]);
+        assert!(keypad.damage(&keys).is_empty());
+    }
+}
@@ -2,19 +2,53 @@
 
 type Callback = fn(&mut App, &Connection, WlEvent<'_>);
 
+/// Emulation timer period in milliseconds
+pub(crate) const TICK_MS: u64 = 24;
+
 #[derive(Debug)]
 pub struct App {
     pub(crate) wl_display: wl_display::WlDisplay,
     pub(crate) wl_registry: wl_registry::WlRegistry,
     pub(crate) wl_compositor: Option<wl_compositor::WlCompositor>,
+    pub(crate) wl_subcompositor: Option<wl_subcompositor::WlSubcompositor>,
     pub(crate) viewporter: Option<WpViewporter>,
+    pub(crate) fractional_scale_mgr: Option<WpFractionalScaleManagerV1>,
+    pub(crate) idle_inhibit_mgr: Option<ZwpIdleInhibitManagerV1>,
+    /// Keeps the screen on while a game runs
+    pub(crate) idle_inhibitor: Option<ZwpIdleInhibitorV1>,
+
+    pub(crate) outputs: Vec<output::Output>,
+    pub(crate) fullscreen_output: Option<String>,
 
     pub(crate) wl_seat: Option<wl_seat::WlSeat>,
     pub(crate) wl_keyboard: Option<wl_keyboard::WlKeyboard>,
+    pub(crate) wl_pointer: Option<wl_pointer::WlPointer>,
+    pub(crate) wl_touch: Option<wl_touch::WlTouch>,
+    /// Last pointer position on the surface
+    pub(crate) pointer_pos: (i32, i32),
+
+    pub(crate) data_device_mgr: Option<wl_data_device_manager::WlDataDeviceManager>,
+    pub(crate) data_device: Option<wl_data_device::WlDataDevice>,
+    pub(crate) offers: Vec<dnd::Offer>,
+    /// Offer being dragged over the window
+    pub(crate) drag_offer: Option<u32>,
+    /// Dropped offer whose uri list is still coming in
+    pub(crate) drop_read: Option<dnd::DropRead>,
+    /// Screenshot we're offering as the selection
+    pub(crate) clipboard: Option<(wl_data_source::WlDataSource, Vec<u8>)>,
+    /// Last key press, for requests that need user input
+    pub(crate) key_serial: u32,
+    /// Video being written and when it started
+    pub(crate) recorder: Option<(record::Recorder, std::time::Instant)>,
+    pub(crate) record_format: record::Format,
 
     pub(crate) wl_shm: Option<wl_shm::WlShm>,
-    pub(crate) wl_buffer: Option<wl_buffer::WlBuffer>,
-    pub(crate) shm: Option<shm::ShmData>,
+    pub(crate) buffers: Option<buffers::BufferPool>,
+    /// The window's pool while the picture surface is showing and the other
+    /// way around, kept so toasts don't reallocate
+    pub(crate) other_buffers: Option<buffers::BufferPool>,
+    /// Outstanding wl_surface.frame, no redraws until it's done
+    pub(crate) frame_callback: Option<wl_callback::WlCallback>,
 
     pub(crate) xdg_decoration_mgr: Option<ZxdgDecorationManagerV1>,
     pub(crate) xdg_base: Option<xdg_wm_base::XdgWmBase>,
@@ -24,15 +58,61 @@ pub struct App {
     pub(crate) xdg_surface: Option<xdg_surface::XdgSurface>,
     pub(crate) xdg_toplevel: Option<xdg_toplevel::XdgToplevel>,
     pub(crate) viewport: Option<WpViewport>,
+    /// The picture at native resolution, see picture.rs
+    pub(crate) picture_surface: Option<wl_surface::WlSurface>,
+    pub(crate) picture_subsurface: Option<wl_subsurface::WlSubsurface>,
+    pub(crate) picture_viewport: Option<WpViewport>,
+    /// Where the picture surface is in the window while it's showing
+    pub(crate) picture_dst: Option<render::Rect>,
+    /// 1x1 buffers the window shows behind the picture surface
+    pub(crate) background: Option<buffers::BufferPool>,
+    pub(crate) fractional_scale: Option<WpFractionalScaleV1>,
+    /// From wp_fractional_scale_v1, in 120ths
+    pub(crate) preferred_scale: Option<u32>,
+    /// From wl_surface.preferred_buffer_scale
+    pub(crate) buffer_scale: i32,
     pub(crate) toplevel_deco: Option<ZxdgToplevelDecorationV1>,
     pub(crate) deco_mode: Option<zxdg_toplevel_decoration_v1::Mode>,
     pub(crate) deco_mode_changed: bool,
     pub(crate) configured: bool,
+    pub(crate) win_state: xdg::WindowState,
+    /// Size to go back to once no longer fullscreen, maximized or tiled
+    pub(crate) floating_size: (i32, i32),
 
     pub(crate) win_height: i32,
     pub(crate) win_width: i32,
+    pub(crate) scaling: render::Scaling,
+    /// Custom palette first if there is one, then the built-in themes
+    pub(crate) palettes: Vec<palette::Palette>,
+    pub(crate) palette: usize,
+    pub(crate) display: display::Display,
+    pub(crate) filters: Vec<filter::Filter>,
+    /// Brightness masks for the current buffer size, `None` without filters
+    pub(crate) masks: Option<filter::Masks>,
+    /// On-screen keypad, `None` when it's off
+    pub(crate) keypad: Option<keypad::Keypad>,
+    /// Keyboard keys holding down each CHIP-8 key, more than one can be
+    /// bound to it. The keypad's holds are its own, see `sync_keys`.
+    pub(crate) keyboard_keys: [u8; 16],
 
     pub(crate) chip8: chip8::Chip8,
+    pub(crate) tracer: Option<trace::Tracer>,
+    pub(crate) halted: Option<chip8::Error>,
+    pub(crate) rom_path: String,
+    pub(crate) backend: chip8::Backend,
+    pub(crate) paused: bool,
+    /// Ticks to run while paused
+    pub(crate) advance: u64,
+    /// Instructions per tick
+    pub(crate) speed: u64,
+    pub(crate) fast_forward: bool,
+    pub(crate) title: String,
+    pub(crate) osd: osd::Osd,
+    pub(crate) keymap: Option<keymap::Keymap>,
+    pub(crate) bindings: bindings::Bindings,
+    /// Has keyboard focus
+    pub(crate) focused: bool,
+    pub(crate) pause_unfocused: bool,
 
     pub(crate) cbs: Vec<(u32, Callback)>,
     pub exit: bool,
@@ -48,22 +128,73 @@ impl App {
             wl_registry,
             wl_display,
             wl_compositor: None,
+            outputs: Vec::new(),
+            fullscreen_output: None,
             wl_seat: None,
             wl_keyboard: None,
+            wl_pointer: None,
+            wl_touch: None,
+            data_device_mgr: None,
+            data_device: None,
+            offers: Vec::new(),
+            drag_offer: None,
+            drop_read: None,
+            clipboard: None,
+            key_serial: 0,
+            recorder: None,
+            record_format: record::Format::default(),
+            pointer_pos: (0, 0),
             wl_shm: None,
             exit: false,
-            shm: None,
             xdg_base: None,
             base_surface: None,
             xdg_surface: None,
             xdg_toplevel: None,
-            wl_buffer: None,
+            buffers: None,
+            other_buffers: None,
+            frame_callback: None,
             configured: false,
+            win_state: xdg::WindowState::default(),
+            floating_size: (0, 0),
+            wl_subcompositor: None,
             viewporter: None,
+            fractional_scale_mgr: None,
+            idle_inhibit_mgr: None,
+            idle_inhibitor: None,
             viewport: None,
+            picture_surface: None,
+            picture_subsurface: None,
+            picture_viewport: None,
+            picture_dst: None,
+            background: None,
+            fractional_scale: None,
+            preferred_scale: None,
+            buffer_scale: 1,
             win_height: 0,
             win_width: 0,
+            scaling: render::Scaling::default(),
+            palettes: Vec::new(),
+            palette: 0,
+            display: display::Display::new(display::Persistence::Off, display::DEFAULT_DECAY),
+            filters: Vec::new(),
+            masks: None,
+            keypad: None,
+            keyboard_keys: [0; 16],
             chip8: chip8::Chip8::new(),
+            tracer: None,
+            halted: None,
+            rom_path: String::new(),
+            backend: chip8::Backend::default(),
+            paused: false,
+            advance: 0,
+            speed: 1,
+            fast_forward: false,
+            title: String::new(),
+            osd: osd::Osd::new(false),
+            keymap: None,
+            bindings: bindings::Bindings::default(),
+            focused: false,
+            pause_unfocused: false,
             xdg_decoration_mgr: None,
             toplevel_deco: None,
             deco_mode: None,
@@ -99,11 +230,18 @@ impl App {
                     wl_compositor::WlCompositor::INTERFACE => {
                         self.wl_compositor = Some(self.wl_registry.bind(conn.writer(), name, interface, version));
                     },
+                    wl_subcompositor::WlSubcompositor::INTERFACE => {
+                        self.wl_subcompositor = Some(self.wl_registry.bind(conn.writer(), name, interface, version));
+                    },
                     wl_seat::WlSeat::INTERFACE => {
                         let wl_seat = self.wl_registry.bind::<wl_seat::WlSeat>(conn.writer(), name, interface, version);
                         self.add_callback(wl_seat.id(), Self::on_wlseat);
                         self.wl_seat = Some(wl_seat);
                     },
+                    wl_data_device_manager::WlDataDeviceManager::INTERFACE => {
+                        let version = version.min(3); // dnd actions
+                        self.data_device_mgr = Some(self.wl_registry.bind(conn.writer(), name, interface, version));
+                    },
                     xdg_wm_base::XdgWmBase::INTERFACE => {
                         let xdg_base = self.wl_registry.bind::<xdg_wm_base::XdgWmBase>(conn.writer(), name, interface, version);
                         self.add_callback(xdg_base.id(), Self::on_xdgbase);
@@ -112,59 +250,134 @@ impl App {
                     WpViewporter::INTERFACE => {
                         self.viewporter = Some(self.wl_registry.bind(conn.writer(), name, interface, version));
                     }
+                    WpFractionalScaleManagerV1::INTERFACE => {
+                        self.fractional_scale_mgr = Some(self.wl_registry.bind(conn.writer(), name, interface, version));
+                    }
+                    ZwpIdleInhibitManagerV1::INTERFACE => {
+                        self.idle_inhibit_mgr = Some(self.wl_registry.bind(conn.writer(), name, interface, version));
+                    }
                     ZxdgDecorationManagerV1::INTERFACE => {
                         self.xdg_decoration_mgr = Some(self.wl_registry.bind(conn.writer(), name, interface, version));
                     }
+                    wl_output::WlOutput::INTERFACE => {
+                        let version = version.min(4); // name event
+                        let wl_output = self.wl_registry.bind::<wl_output::WlOutput>(conn.writer(), name, interface, version);
+                        self.add_callback(wl_output.id(), Self::on_wloutput);
+                        self.outputs.push(output::Output { wl_output, global: name, version, name: None });
+                    }
                     _ => {}
                 }
             },
             wl_registry::Event::GlobalRemove { name } => {
+                if let Some(i) = self.outputs.iter().position(|o| o.global == name) {
+                    let output = self.outputs.swap_remove(i);
+                    if output.version >= 3 {
+                        output.wl_output.release(conn.writer());
+                    }
+                }
             },
         }
     }
 
-    pub fn init(&mut self, conn: &Connection, rom_path: &str) -> Result<(), &'static str> {
+    pub fn init(&mut self, conn: &Connection, opts: &Options) -> Result<(), &'static str> {
+        let glyphs = match opts.font_file.as_deref() {
+            Some(path) => match font::load(path) {
+                Ok(glyphs) => glyphs,
+                Err(e) => {
+                    log!(ERR, "'{}' - {}", path, e);
+                    return Err("Failed to load font");
+                }
+            },
+            None => *opts.font.glyphs(),
+        };
+        if let Err(e) = self.chip8.set_font(&glyphs, opts.font_base) {
+            log!(ERR, "{}", e);
+            return Err("Invalid font base address");
+        }
+
+        let custom = match opts.palette_file.as_deref() {
+            Some(path) => match palette::load(path) {
+                Ok(palette) => Some(palette),
+                Err(e) => {
+                    log!(ERR, "'{}' - {}", path, e);
+                    return Err("Failed to load palette");
+                }
+            },
+            None => opts.colors,
+        };
+        self.palettes = custom.into_iter().collect();
+        self.palette = match custom {
+            Some(_) => 0,
+            None => palette::Theme::ALL.iter().position(|t| *t == opts.theme).unwrap_or(0),
+        };
+        self.palettes.extend(palette::Theme::ALL.iter().map(|t| t.palette()));
+        self.display = display::Display::new(opts.persistence, opts.decay);
+        self.filters = opts.filters.clone();
+        self.keypad = opts.keypad.then(keypad::Keypad::default);
+        self.osd.stats = opts.stats;
+        self.pause_unfocused = opts.pause_unfocused;
+        self.record_format = opts.record_format;
+
+        self.bindings = bindings::Bindings::preset(opts.keys);
+        if let Some(path) = opts.key_file.as_deref()
+            && let Err(e) = self.bindings.load(path)
+        {
+            log!(ERR, "'{}' - {}", path, e);
+            return Err("Failed to load key bindings");
+        }
+
+        let rom_path = opts.rom_path.as_str();
         if let Err(e) = self.chip8.load(rom_path) {
             log!(ERR, "'{}' - {}", rom_path, e);
             return Err("Failed to load rom");
         }
 
+        if let Err(e) = self.chip8.set_backend(opts.backend) {
+            log!(ERR, "{} backend - {}", opts.backend.name(), e);
+            return Err("Failed to set up the emulation backend");
+        }
+        // for resets
+        self.rom_path = opts.rom_path.clone();
+        self.backend = opts.backend;
+        if let Some(path) = opts.record.as_deref() {
+            self.start_recording(path);
+        }
+
+        if let Some(trace) = opts.trace.as_ref() {
+            match trace::Tracer::new(trace) {
+                Ok(tracer) => self.tracer = Some(tracer),
+                Err(e) => {
+                    log!(ERR, "'{}' - {}", trace.path, e);
+                    return Err("Failed to create trace file");
+                }
+            }
+        }
+
         let wl_compositor = self
             .wl_compositor
             .as_ref()
             .ok_or("wl_compositor is unavailable")?;
-        let viewporter = self
-            .viewporter
-            .as_ref()
-            .ok_or("Viewporter is unavailable")?;
         let wl_shm = self.wl_shm.as_ref().ok_or("wl_shm is unavailable")?;
         let xdg_base = self.xdg_base.as_ref().ok_or("xdg_base is unavailable")?;
 
-        // Rust please let us have access to err in let-else syntax,
-        // just like Zig
-        let size = chip8::WIDTH * chip8::SCALE * chip8::HEIGHT * chip8::SCALE * 4;
-        let (shm, fd) = match shm::ShmData::new(size) {
-            Ok(d) => d,
-            Err(e) => {
-                log!(ERR, "{}", e);
-                return Err("Couldn't allocate shared memoery");
-            }
-        };
-
-        let pool = wl_shm.create_pool(conn.writer(), fd, size as i32);
-        let wl_buffer = pool.create_buffer(
-            conn.writer(),
-            0,
-            (chip8::SCALE * chip8::WIDTH) as i32,
-            (chip8::SCALE * chip8::HEIGHT) as i32,
-            (chip8::SCALE * chip8::WIDTH) as i32 * 4,
-            wl_shm::Format::Xrgb8888 as u32,
-        );
-        pool.destroy(conn.writer());
-
         let wl_surface = wl_compositor.create_surface(conn.writer());
-        let viewport = viewporter.get_viewport(conn.writer(), &wl_surface);
+        // without it buffers are just drawn at the window size
+        let viewport = self.viewporter.as_ref().map(|v| v.get_viewport(conn.writer(), &wl_surface));
+        let picture = self.create_picture(conn, &wl_surface);
+        if picture.is_none() {
+            log!(DEBUG, "wp_viewporter or wl_subcompositor is unavailable, scaling in software");
+        }
+        // fractional scales only work out with a viewport
+        let fractional_scale = match viewport {
+            Some(_) => self.fractional_scale_mgr.as_ref().map(|m| m.get_fractional_scale(conn.writer(), &wl_surface)),
+            None => None,
+        };
         let xdg_surface = xdg_base.get_xdg_surface(conn.writer(), &wl_surface);
+        // for dropping roms on the window
+        let data_device = match (self.data_device_mgr.as_ref(), self.wl_seat.as_ref()) {
+            (Some(mgr), Some(wl_seat)) => Some(mgr.get_data_device(conn.writer(), wl_seat)),
+            _ => None,
+        };
         let xdg_toplevel = xdg_surface.get_toplevel(conn.writer());
 
         if let Some(deco_mgr) = self.xdg_decoration_mgr.as_ref() {
@@ -176,32 +389,62 @@ impl App {
             self.deco_mode = Some(mode);
         }
 
-        xdg_toplevel.set_title(conn.writer(), "eightpotatochips");
         xdg_toplevel.set_app_id(conn.writer(), "github.evillary.eightpotatochips");
+        self.scaling = opts.scaling;
         self.win_height = (chip8::SCALE * chip8::HEIGHT) as i32;
         self.win_width = (chip8::SCALE * chip8::WIDTH) as i32;
-        xdg_toplevel.set_min_size(conn.writer(), self.win_width, self.win_height);
-        xdg_toplevel.set_max_size(conn.writer(), self.win_width, self.win_height);
-        viewport.set_destination(conn.writer(), self.win_width, self.win_height);
+        // the picture keeps its size under our own title bar
+        if self.csd() {
+            self.win_height += csd::TITLE_H;
+        }
+        self.floating_size = (self.win_width, self.win_height);
+        self.fullscreen_output = opts.output.clone();
+        if opts.fullscreen {
+            xdg_toplevel.set_fullscreen(conn.writer(), self.fullscreen_output());
+        }
+        // anything goes, as long as 1x fits
+        xdg_toplevel.set_min_size(conn.writer(), chip8::WIDTH as i32, chip8::HEIGHT as i32);
+        if let Some(viewport) = viewport.as_ref() {
+            viewport.set_destination(conn.writer(), self.win_width, self.win_height);
+        }
         wl_surface.commit(conn.writer());
 
         self.add_callback(xdg_toplevel.id(), Self::on_xdgtoplevel);
         self.add_callback(wl_surface.id(), Self::on_wlsurface);
-        self.add_callback(wl_buffer.id(), Self::on_wlbuffer);
         self.add_callback(xdg_surface.id(), Self::on_xdgsurface);
+        if let Some(fractional_scale) = fractional_scale.as_ref() {
+            self.add_callback(fractional_scale.id(), Self::on_fractional_scale);
+        }
+        if let Some(data_device) = data_device.as_ref() {
+            self.add_callback(data_device.id(), Self::on_data_device);
+        }
 
-        self.wl_buffer = Some(wl_buffer);
         self.base_surface = Some(wl_surface);
         self.xdg_toplevel = Some(xdg_toplevel);
         self.xdg_surface = Some(xdg_surface);
-        self.shm = Some(shm);
-        self.viewport = Some(viewport);
+        self.viewport = viewport;
+        if let Some((surface, subsurface, viewport)) = picture {
+            self.picture_surface = Some(surface);
+            self.picture_subsurface = Some(subsurface);
+            self.picture_viewport = Some(viewport);
+        }
+        self.fractional_scale = fractional_scale;
+        self.data_device = data_device;
+        self.update_title(conn);
+
+        // Rust please let us have access to err in let-else syntax,
+        // just like Zig
+        let (width, height) = self.buffer_size();
+        if let Err(e) = self.alloc_buffer(conn, width, height) {
+            log!(ERR, "{}", e);
+            return Err("Couldn't allocate shared memoery");
+        }
 
         Ok(())
     }
 
     pub fn enter_loop(&mut self, conn: &Connection) -> std::io::Result<()> {
-        let ticker_fd = Ticker::new(24)?;
+        let ticker_fd = Ticker::new(TICK_MS)?;
         let mut pollfds = [
             libc::pollfd {
                 fd: conn.display_fd(),
@@ -213,9 +456,16 @@ impl App {
                 events: libc::POLLIN | libc::POLLHUP,
                 revents: 0,
             },
+            // a drop's pipe while one's coming in, poll skips negative fds
+            libc::pollfd {
+                fd: -1,
+                events: libc::POLLIN,
+                revents: 0,
+            },
         ];
 
         while !self.exit {
+            pollfds[2].fd = self.drop_read.as_ref().map_or(-1, |d| d.fd());
             conn.flush()?;
             let ret = unsafe {
                 syscall(libc::poll(
@@ -225,7 +475,8 @@ impl App {
                 ))
             }?;
 
-            for pollfd in pollfds {
+            // the drop pipe hangs up once the whole list is written
+            for pollfd in &pollfds[..2] {
                 if pollfd.revents & libc::POLLHUP > 0 {
                     log!(ERR, "{} fd hung up", pollfd.fd);
                     return Err(std::io::Error::new(
@@ -239,8 +490,15 @@ impl App {
             if pollfds[1].revents & libc::POLLIN > 0 {
                 let tickles = ticker_fd.read_timer()?;
                 // in case some ticks are missed
-                for _ in 0..tickles {
-                    self.chip8.cycle();
+                let mut budget = self.budget(tickles as u64);
+                while budget > 0 && self.halted.is_none() {
+                    budget -= self.step(budget);
+                }
+                // might have just halted
+                self.update_title(conn);
+                let status = self.osd_status();
+                if self.osd.update(std::time::Instant::now(), status) {
+                    self.chip8.need_redraw = true;
                 }
             }
 
@@ -255,49 +513,242 @@ impl App {
                 };
             }
 
+            if pollfds[2].revents & (libc::POLLIN | libc::POLLHUP) > 0 {
+                self.read_drop(conn);
+            }
+
             if self.chip8.need_redraw {
                 self.redraw(conn);
             }
+            self.record_frames();
+            self.update_idle_inhibit(conn);
+        }
+        Ok(())
+    }
+
+    // Runs up to `budget` instructions, returns how many were used up
+    fn step(&mut self, budget: u64) -> u64 {
+        // One at a time so every instruction makes it into the trace
+        let budget = match self.tracer.as_mut() {
+            Some(tracer) => {
+                if let Err(e) = tracer.log(&self.chip8) {
+                    log!(ERR, "Tracing stopped: {}", e);
+                    self.tracer = None;
+                }
+                1
+            }
+            None => budget,
+        };
+        match self.chip8.run(budget) {
+            Ok(ran) => ran,
+            Err(e) => {
+                log!(ERR, "Emulation halted: {}", e);
+                self.halted = Some(e);
+                budget
+            }
         }
+    }
+
+    // (Re)creates the buffer pool at `width`x`height`, the old buffers are
+    // destroyed, the compositor keeps whatever it still needs from them.
+    fn alloc_buffer(&mut self, conn: &Connection, width: i32, height: i32) -> std::io::Result<()> {
+        let wl_shm = self.wl_shm.as_ref().unwrap();
+        let pool = buffers::BufferPool::new(conn, wl_shm, width, height)?;
+
+        if let Some(old) = self.buffers.take() {
+            old.destroy(conn);
+        }
+        for id in pool.ids().collect::<Vec<_>>() {
+            self.add_callback(id, Self::on_wlbuffer);
+        }
+        self.buffers = Some(pool);
         Ok(())
     }
 
+    // Only the emulated screen is on screen, so a native resolution buffer
+    // the compositor stretches shows the same thing with a fraction of the
+    // memory and copying
+    fn native(&self) -> bool {
+        self.picture_surface.is_some()
+            && self.native_rect().is_some()
+            && self.filters.is_empty()
+            && self.keypad.is_none()
+            && !self.csd()
+            && self.osd.is_empty()
+    }
+
+    // Buffers match the window unless the compositor does the scaling
+    fn buffer_size(&self) -> (i32, i32) {
+        match self.native() {
+            true => (chip8::WIDTH as i32, chip8::HEIGHT as i32),
+            false => (self.to_device(self.win_width), self.to_device(self.win_height)),
+        }
+    }
+
+    // Where the picture and the keypad go in `content`
+    pub(crate) fn picture_rect(&self, content: render::Rect) -> (render::Rect, render::Rect) {
+        let (picture, panel) = match self.keypad {
+            Some(_) => keypad::split(content),
+            None => (content, render::Rect::default()),
+        };
+        let mut dst = render::fit(self.scaling, picture.width, picture.height, chip8::WIDTH, chip8::HEIGHT);
+        dst.x += picture.x;
+        dst.y += picture.y;
+        (dst, panel)
+    }
+
+    // Everything drawn since the last frame callback goes out in one commit,
+    // nothing at all while the compositor holds the callback back (hidden,
+    // minimized, on another workspace)
     pub fn redraw(&mut self, conn: &Connection) {
+        if self.frame_callback.is_some() {
+            return;
+        }
+        self.present(conn);
+    }
+
+    // Draws and commits right away, configures can't wait for a frame
+    // callback
+    pub(crate) fn present(&mut self, conn: &Connection) {
         if !self.configured {
             return;
         }
-        let Some(shm) = self.shm.as_mut() else {
+        let native = self.native();
+        let (width, height) = self.buffer_size();
+        // buffers stay with one surface, switching brings back its own
+        if native != self.picture_dst.is_some() {
+            std::mem::swap(&mut self.buffers, &mut self.other_buffers);
+            if let Some(buffers) = self.buffers.as_mut() {
+                buffers.damage_all();
+            }
+        }
+        match native {
+            true => self.show_picture(conn),
+            false => self.hide_picture(conn),
+        }
+        let size = self.buffers.as_ref().map(|b| (b.width, b.height));
+        if size != Some((width, height))
+            && let Err(e) = self.alloc_buffer(conn, width, height)
+        {
+            log!(ERR, "Couldn't resize buffer: {}", e);
+            return;
+        }
+        // goes along with the commit that brings the new buffer, the
+        // viewport does this job otherwise
+        if size != Some((width, height))
+            && self.viewport.is_none()
+            && let Some(wl_surface) = self.base_surface.as_ref()
+        {
+            wl_surface.set_buffer_scale(conn.writer(), self.buffer_scale);
+        }
+        let content = self.content_area(width, height);
+        let rom_name = self.rom_name();
+        let (dst, panel) = self.picture_rect(content);
+        let Some(buffers) = self.buffers.as_mut() else {
             return;
         };
-        let Some(wl_surface) = self.base_surface.as_ref() else {
+        let surface = match native {
+            true => self.picture_surface.as_ref(),
+            false => self.base_surface.as_ref(),
+        };
+        let Some(wl_surface) = surface else {
+            return;
+        };
+        // all of them still with the compositor, try again once one comes back
+        let Some(index) = buffers.acquire() else {
+            self.chip8.need_redraw = true;
             return;
         };
 
-        // mutli thread this shit??
-        let pixels_out = shm.as_slice_mut::<u32>();
-        for (y, row) in pixels_out.chunks_mut(chip8::WIDTH * chip8::SCALE).enumerate() {
-            let src_y = y / chip8::SCALE;
-            for (x, pix) in row.iter_mut().enumerate() {
-                let src_x = x / chip8::SCALE;
-                if self.chip8.framebuffer[src_y * chip8::WIDTH + src_x] != 0 {
-                    *pix = 0x79b67b;
-                } else {
-                    *pix = 0x000000;
-                }
+        if !self.chip8.dirty.is_empty() {
+            self.osd.count_frame();
+        }
+        let palette = &self.palettes[self.palette];
+        let animating = self.display.compose(&self.chip8.framebuffer, palette, &mut self.chip8.dirty, std::time::Instant::now());
+
+        let bloom = self.filters.contains(&filter::Filter::Bloom);
+        let colors = match bloom {
+            true => std::borrow::Cow::Owned(filter::bloom(&self.display.colors, chip8::WIDTH, chip8::HEIGHT)),
+            false => std::borrow::Cow::Borrowed(&self.display.colors[..]),
+        };
+
+        let stale = self.masks.as_ref().map(|m| (m.width, m.height, m.dst));
+        if stale != Some((width, height, dst)) {
+            let masks = filter::Masks::new(&self.filters, width, height, dst, chip8::WIDTH, chip8::HEIGHT);
+            self.masks = masks;
+        }
+        let screen = render::Rect { x: 0, y: 0, width: chip8::WIDTH as i32, height: chip8::HEIGHT as i32 };
+        let changed: Vec<_> = self
+            .chip8
+            .dirty
+            .drain(..)
+            // bloom spills light one pixel further
+            .map(|r| match bloom {
+                true => render::Rect { x: r.x - 1, y: r.y - 1, width: r.width + 2, height: r.height + 2 }.intersect(&screen),
+                false => r,
+            })
+            .map(|r| render::scale_rect(r, chip8::WIDTH, chip8::HEIGHT, dst))
+            .chain(self.osd.damage(content))
+            .chain(self.keypad.as_mut().map_or(Vec::new(), |k| {
+                k.panel = panel;
+                k.damage(&self.chip8.keys)
+            }))
+            .collect();
+        buffers.damage(&changed);
+        // this buffer may be a few frames behind, not just this one
+        let damage = buffers.take_damage(index);
+
+        let mut canvas = render::Canvas {
+            pixels: buffers.pixels(index),
+            width: width as usize,
+            height: height as usize,
+        };
+        let src = render::Source {
+            colors: &colors,
+            width: chip8::WIDTH,
+            height: chip8::HEIGHT,
+        };
+        for clip in damage.iter() {
+            render::draw(&src, &mut canvas, dst, palette[0], *clip, self.masks.as_ref());
+            if let Some(keypad) = self.keypad.as_ref() {
+                keypad.draw(&mut canvas, palette, *clip);
+            }
+            if content.y > 0 {
+                csd::draw(&mut canvas, palette, &rom_name, content.y, *clip);
             }
         }
-        // for (cell, out_p) in self.chip8.framebuffer.iter().zip(pixels_out) {
-        //     if *cell > 0 {
-        //         *out_p = 0x79b67b;
-        //     } else {
-        //         *out_p = 0x000000;
-        //         // *out_p = 0x141618;
-        //     }
-        // }
-        wl_surface.attach(conn.writer(), self.wl_buffer.as_ref(), 0, 0);
-        wl_surface.damage_buffer(conn.writer(), 0, 0, i32::MAX, i32::MAX);
+        self.osd.draw(&mut canvas, content);
+        buffers.attach(conn, wl_surface, index);
+        for rect in damage.iter() {
+            wl_surface.damage_buffer(conn.writer(), rect.x, rect.y, rect.width, rect.height);
+        }
+        let frame_callback = match self.frame_callback {
+            Some(_) => None,
+            None => Some(wl_surface.frame(conn.writer())),
+        };
         wl_surface.commit(conn.writer());
-        self.chip8.need_redraw = false;
+        // the picture's commit waits for the window's
+        if native && let Some(base_surface) = self.base_surface.as_ref() {
+            base_surface.commit(conn.writer());
+        }
+        // fading pixels want another frame even if nothing was drawn
+        self.chip8.need_redraw = animating;
+
+        if let Some(frame_callback) = frame_callback {
+            self.add_callback(frame_callback.id(), Self::on_frame);
+            self.frame_callback = Some(frame_callback);
+        }
+    }
+
+    #[rustfmt::skip]
+    fn on_frame(&mut self, conn: &Connection, event: WlEvent<'_>) {
+        let Some(frame_callback) = self.frame_callback.take() else {
+            return;
+        };
+        match frame_callback.parse_event(conn.reader(), event) {
+            // enter_loop picks up `need_redraw` right after dispatching
+            wl_callback::Event::Done { .. } => {},
+        }
     }
 
     #[rustfmt::skip]
@@ -305,21 +756,41 @@ impl App {
         let Some(wl_surface) = self.base_surface.as_ref() else {
             return;
         };
-        match wl_surface.parse_event(conn.reader(), event) {
-            _ => {}
+        // wl_surface.enter/leave don't matter, the compositor works out the
+        // scale from them
+        if let wl_surface::Event::PreferredBufferScale { factor } = wl_surface.parse_event(conn.reader(), event)
+            && self.buffer_scale != factor
+        {
+            self.buffer_scale = factor;
+            self.chip8.need_redraw = true;
         }
     }
 
     #[rustfmt::skip]
     fn on_wlbuffer(&mut self, conn: &Connection, event: WlEvent<'_>) {
-        let Some(wl_buffer) = self.wl_buffer.as_ref() else {
+        let id = event.header.id;
+        // releases of buffers from before a resize don't matter anymore
+        let pools = [self.buffers.as_mut(), self.other_buffers.as_mut(), self.background.as_mut()];
+        let Some(buffers) = pools.into_iter().flatten().find(|b| b.get(id).is_some()) else {
+            return;
+        };
+        let Some(wl_buffer) = buffers.get(id) else {
             return;
         };
         match wl_buffer.parse_event(conn.reader(), event) {
-            _ => {}
+            wl_buffer::Event::Release => buffers.release(id),
         }
     }
 
+    pub(crate) fn cycle_palette(&mut self) {
+        self.palette = (self.palette + 1) % self.palettes.len();
+        self.osd.toast(format!("Palette {}/{}", self.palette + 1, self.palettes.len()));
+        if let Some(buffers) = self.buffers.as_mut() {
+            buffers.damage_all();
+        }
+        self.chip8.need_redraw = true;
+    }
+
     fn add_callback(&mut self, id: u32, cb: Callback) {
         self.cbs.push((id, cb));
     }
@@ -343,12 +814,42 @@ use crate::shared::syscall;
 use crate::shared::shm;
 use crate::shared::ticker::Ticker;
 use scr_protocols::viewporter::{wp_viewport::WpViewport, wp_viewporter::WpViewporter};
+use scr_protocols::fractional_scale_v1::{wp_fractional_scale_manager_v1::WpFractionalScaleManagerV1, wp_fractional_scale_v1::{self, WpFractionalScaleV1}};
+use scr_protocols::idle_inhibit_unstable_v1::{zwp_idle_inhibit_manager_v1::ZwpIdleInhibitManagerV1, zwp_idle_inhibitor_v1::ZwpIdleInhibitorV1};
 use scr_protocols::xdg_shell::*;
 use scr_protocols::xdg_decoration_unstable_v1::{*, zxdg_decoration_manager_v1::ZxdgDecorationManagerV1, zxdg_toplevel_decoration_v1::ZxdgToplevelDecorationV1};
 use scratchway::log;
 use scratchway::prelude::*;
 use scratchway::wayland::*;
 
+pub mod bench;
+pub mod headless;
+mod bindings;
+mod buffers;
 mod chip8;
+mod control;
+mod csd;
+mod display;
+mod dnd;
+mod filter;
+mod font;
 mod input;
+#[cfg(target_arch = "x86_64")]
+mod jit;
+mod keymap;
+mod keypad;
+mod options;
+mod osd;
+mod output;
+mod palette;
+mod png;
+mod picture;
+mod pointer;
+mod record;
+mod render;
+mod screenshot;
+mod scale;
+mod trace;
 mod xdg;
+
+pub use options::Options;
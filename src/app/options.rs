@@ -0,0 +1,239 @@
+use super::bindings::Preset;
+use super::chip8::Backend;
+use super::display::{Persistence, DEFAULT_DECAY};
+use super::filter::{self, Filter};
+use super::font::Font;
+use super::palette::{self, Palette, Theme};
+use super::record::Format;
+use super::render::Scaling;
+use super::trace::{TraceFormat, TraceOptions, Trigger};
+
+#[derive(Debug)]
+pub struct Options {
+    pub rom_path: String,
+    pub trace: Option<TraceOptions>,
+    pub backend: Backend,
+    /// Run this many cycles headless on every backend and report the speed
+    pub bench: Option<u64>,
+    pub font: Font,
+    /// Raw 80 byte font, overrides `font`
+    pub font_file: Option<String>,
+    pub font_base: usize,
+    pub scaling: Scaling,
+    pub theme: Theme,
+    /// Custom colours, take the place of `theme`
+    pub colors: Option<Palette>,
+    /// Same format as `colors`, read at startup
+    pub palette_file: Option<String>,
+    pub persistence: Persistence,
+    /// Frames a cleared pixel takes to fade out under `Persistence::Phosphor`
+    pub decay: u8,
+    /// Any combination, they all stack
+    pub filters: Vec<Filter>,
+    /// FPS and speed in the corner of the window
+    pub stats: bool,
+    /// Stop emulating without keyboard focus
+    pub pause_unfocused: bool,
+    pub keys: Preset,
+    /// Binding file applied on top of `keys`
+    pub key_file: Option<String>,
+    /// Clickable hex keypad next to the picture
+    pub keypad: bool,
+    pub fullscreen: bool,
+    /// Output to go fullscreen on, by connector name
+    pub output: Option<String>,
+    /// Recording that starts right away, format by extension
+    pub record: Option<String>,
+    /// What the record hotkey writes
+    pub record_format: Format,
+    /// Run this many video frames without a window, into `record`
+    pub headless: Option<u64>,
+}
+
+impl Default for Options {
+    fn default() -> Self {
+        Self {
+            rom_path: String::new(),
+            trace: None,
+            backend: Backend::default(),
+            bench: None,
+            font: Font::default(),
+            font_file: None,
+            font_base: 0,
+            scaling: Scaling::default(),
+            theme: Theme::default(),
+            colors: None,
+            palette_file: None,
+            persistence: Persistence::default(),
+            decay: DEFAULT_DECAY,
+            filters: Vec::new(),
+            stats: false,
+            keypad: false,
+            pause_unfocused: false,
+            keys: Preset::default(),
+            key_file: None,
+            fullscreen: false,
+            output: None,
+            record: None,
+            record_format: Format::default(),
+            headless: None,
+        }
+    }
+}
+
+impl Options {
+    pub fn from_args(mut args: impl Iterator<Item = String>) -> Result<Self, String> {
+        let mut opts = Self::default();
+        let mut rom_path = None;
+
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "--trace" => {
+                    let path = next_value(&mut args, &arg)?;
+                    opts.trace.get_or_insert_with(TraceOptions::default).path = path;
+                }
+                "--trace-range" => {
+                    let value = next_value(&mut args, &arg)?;
+                    let (lo, hi) = value
+                        .split_once('-')
+                        .ok_or_else(|| format!("'{}' expects <start>-<end>", arg))?;
+                    let range = (parse_num(lo)? as usize, parse_num(hi)? as usize);
+                    opts.trace.get_or_insert_with(TraceOptions::default).range = Some(range);
+                }
+                "--trace-format" => {
+                    let name = next_value(&mut args, &arg)?;
+                    let format = *TraceFormat::ALL
+                        .iter()
+                        .find(|f| f.name() == name)
+                        .ok_or_else(|| format!("Unknown trace format '{}'", name))?;
+                    opts.trace.get_or_insert_with(TraceOptions::default).format = format;
+                }
+                "--trace-start" => {
+                    let trigger = Trigger::parse(&next_value(&mut args, &arg)?)?;
+                    opts.trace.get_or_insert_with(TraceOptions::default).start = Some(trigger);
+                }
+                "--trace-stop" => {
+                    let trigger = Trigger::parse(&next_value(&mut args, &arg)?)?;
+                    opts.trace.get_or_insert_with(TraceOptions::default).stop = Some(trigger);
+                }
+                "--backend" => {
+                    let name = next_value(&mut args, &arg)?;
+                    opts.backend = *Backend::ALL
+                        .iter()
+                        .find(|b| b.name() == name)
+                        .ok_or_else(|| format!("Unknown backend '{}'", name))?;
+                }
+                "--bench" => {
+                    opts.bench = Some(parse_num(&next_value(&mut args, &arg)?)?);
+                }
+                "--font" => {
+                    let name = next_value(&mut args, &arg)?;
+                    opts.font = *Font::ALL
+                        .iter()
+                        .find(|f| f.name() == name)
+                        .ok_or_else(|| format!("Unknown font '{}'", name))?;
+                }
+                "--font-file" => {
+                    opts.font_file = Some(next_value(&mut args, &arg)?);
+                }
+                "--font-base" => {
+                    opts.font_base = parse_num(&next_value(&mut args, &arg)?)? as usize;
+                }
+                "--scaling" => {
+                    let name = next_value(&mut args, &arg)?;
+                    opts.scaling = *Scaling::ALL
+                        .iter()
+                        .find(|s| s.name() == name)
+                        .ok_or_else(|| format!("Unknown scaling '{}'", name))?;
+                }
+                "--palette" => {
+                    let name = next_value(&mut args, &arg)?;
+                    opts.theme = *Theme::ALL
+                        .iter()
+                        .find(|t| t.name() == name)
+                        .ok_or_else(|| format!("Unknown palette '{}'", name))?;
+                }
+                "--colors" => {
+                    opts.colors = Some(palette::parse(&next_value(&mut args, &arg)?)?);
+                }
+                "--palette-file" => {
+                    opts.palette_file = Some(next_value(&mut args, &arg)?);
+                }
+                "--persistence" => {
+                    let name = next_value(&mut args, &arg)?;
+                    opts.persistence = *Persistence::ALL
+                        .iter()
+                        .find(|p| p.name() == name)
+                        .ok_or_else(|| format!("Unknown persistence '{}'", name))?;
+                }
+                "--decay" => {
+                    let frames = parse_num(&next_value(&mut args, &arg)?)?;
+                    opts.decay = u8::try_from(frames)
+                        .ok()
+                        .filter(|f| *f > 0)
+                        .ok_or_else(|| format!("'{}' expects 1 to 255 frames", arg))?;
+                }
+                "--filter" => {
+                    opts.filters = filter::parse_list(&next_value(&mut args, &arg)?)?;
+                }
+                "--stats" => opts.stats = true,
+                "--pause-unfocused" => opts.pause_unfocused = true,
+                "--keys" => {
+                    let name = next_value(&mut args, &arg)?;
+                    opts.keys = *Preset::ALL
+                        .iter()
+                        .find(|p| p.name() == name)
+                        .ok_or_else(|| format!("Unknown key preset '{}'", name))?;
+                }
+                "--key-file" => {
+                    opts.key_file = Some(next_value(&mut args, &arg)?);
+                }
+                "--keypad" => opts.keypad = true,
+                "--fullscreen" => opts.fullscreen = true,
+                "--output" => {
+                    opts.output = Some(next_value(&mut args, &arg)?);
+                }
+                "--record" => {
+                    let path = next_value(&mut args, &arg)?;
+                    Format::from_path(&path)?;
+                    opts.record = Some(path);
+                }
+                "--record-format" => {
+                    let name = next_value(&mut args, &arg)?;
+                    opts.record_format = *Format::ALL
+                        .iter()
+                        .find(|f| f.name() == name)
+                        .ok_or_else(|| format!("Unknown record format '{}'", name))?;
+                }
+                "--headless" => {
+                    opts.headless = Some(parse_num(&next_value(&mut args, &arg)?)?);
+                }
+                _ if arg.starts_with("--") => return Err(format!("Unknown option '{}'", arg)),
+                _ => rom_path = Some(arg),
+            }
+        }
+
+        if opts.trace.as_ref().is_some_and(|t| t.path.is_empty()) {
+            return Err("Trace filters given without '--trace <file>'".into());
+        }
+        if opts.headless.is_some() && opts.record.is_none() {
+            return Err("'--headless' needs '--record <file>'".into());
+        }
+        opts.rom_path = rom_path.ok_or("No rom provided")?;
+        Ok(opts)
+    }
+}
+
+fn next_value(args: &mut impl Iterator<Item = String>, arg: &str) -> Result<String, String> {
+    args.next().ok_or_else(|| format!("'{}' expects a value", arg))
+}
+
+// Accepts both `0x2A0` and `672`
+pub(crate) fn parse_num(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let res = match s.strip_prefix("0x").or_else(|| s.strip_prefix("0X")) {
+        Some(hex) => u64::from_str_radix(hex, 16),
+        None => s.parse(),
+    };
+    res.map_err(|_| format!("'{}' is not a valid number", s))
+}
@@ -0,0 +1,289 @@
+// On-screen text: a stats corner, what the emulator is up to and short
+// lived messages, drawn over the picture with a built-in 5x7 font.
+use std::time::{Duration, Instant};
+
+use super::filter;
+use super::render::{Canvas, Rect};
+
+pub(crate) const GLYPH_W: i32 = 5;
+pub(crate) const GLYPH_H: i32 = 7;
+/// Glyph plus spacing
+const ADVANCE: i32 = GLYPH_W + 1;
+const LINE: i32 = GLYPH_H + 2;
+const PADDING: i32 = 2;
+const TOAST_TIME: Duration = Duration::from_secs(2);
+const TEXT: u32 = 0xFFFFFF;
+/// How much of the picture shows through behind the text
+const BACKDROP: u32 = 96;
+
+#[derive(Debug)]
+pub(crate) struct Osd {
+    /// FPS and speed in the corner
+    pub stats: bool,
+    toasts: Vec<(String, Instant)>,
+    /// Frames that changed something, counted over the last second
+    frames: u32,
+    since: Instant,
+    fps: u32,
+    lines: Vec<String>,
+    /// Drawn on the last frame, has to be painted over on the next
+    drawn: Option<Rect>,
+}
+
+impl Osd {
+    pub fn new(stats: bool) -> Self {
+        Self {
+            stats,
+            toasts: Vec::new(),
+            frames: 0,
+            since: Instant::now(),
+            fps: 0,
+            lines: Vec::new(),
+            drawn: None,
+        }
+    }
+
+    pub fn toast(&mut self, text: impl Into<String>) {
+        self.toasts.push((text.into(), Instant::now() + TOAST_TIME));
+    }
+
+    pub fn count_frame(&mut self) {
+        self.frames += 1;
+    }
+
+    pub fn fps(&self) -> u32 {
+        self.fps
+    }
+
+    // Nothing to show right now
+    pub fn is_empty(&self) -> bool {
+        self.lines.is_empty()
+    }
+
+    // Works out the text for `now`, `status` lines go first. True when it
+    // differs from what's on screen.
+    pub fn update(&mut self, now: Instant, status: Vec<String>) -> bool {
+        let elapsed = now.duration_since(self.since);
+        if elapsed >= Duration::from_secs(1) {
+            self.fps = (self.frames as u128 * 1000 / elapsed.as_millis()) as u32;
+            self.frames = 0;
+            self.since = now;
+        }
+        self.toasts.retain(|(_, until)| *until > now);
+
+        let mut lines = status;
+        lines.extend(self.toasts.iter().map(|(text, _)| text.clone()));
+        if lines == self.lines {
+            return false;
+        }
+        self.lines = lines;
+        true
+    }
+
+    // Text scale for a `height` tall canvas, roughly a 1/40th line height
+    fn scale(height: i32) -> i32 {
+        (height / (LINE * 40)).max(1)
+    }
+
+    // Where the text box goes, in the top left of `bounds`
+    pub fn area(&self, bounds: Rect) -> Option<Rect> {
+        let longest = self.lines.iter().map(|l| l.chars().count()).max()? as i32;
+        let scale = Self::scale(bounds.height);
+        let rect = Rect {
+            x: bounds.x + PADDING * scale,
+            y: bounds.y + PADDING * scale,
+            width: (longest * ADVANCE + PADDING) * scale,
+            height: (self.lines.len() as i32 * LINE + PADDING) * scale,
+        };
+        Some(rect.intersect(&bounds)).filter(|r| !r.is_empty())
+    }
+
+    // Everything that has to be redrawn this frame for the text to come
+    // and go cleanly: where it was and where it is now
+    pub fn damage(&mut self, bounds: Rect) -> Vec<Rect> {
+        let area = self.area(bounds);
+        let damage = self.drawn.into_iter().chain(area).collect();
+        self.drawn = area;
+        damage
+    }
+
+    // Composites the text onto a freshly drawn canvas, `bounds` has to be
+    // inside it
+    pub fn draw(&self, canvas: &mut Canvas<'_>, bounds: Rect) {
+        let width = canvas.width as i32;
+        let Some(area) = self.area(bounds) else {
+            return;
+        };
+        for y in area.y..area.y + area.height {
+            let row = &mut canvas.pixels[(y * width) as usize..][area.x as usize..(area.x + area.width) as usize];
+            for pix in row {
+                *pix = filter::shade(*pix, BACKDROP);
+            }
+        }
+
+        let scale = Self::scale(bounds.height);
+        let pad = PADDING * scale;
+        for (i, line) in self.lines.iter().enumerate() {
+            let y = area.y + pad + i as i32 * LINE * scale;
+            draw_text(canvas, area.x + pad, y, scale, line, TEXT, area);
+        }
+    }
+}
+
+// `text` with its top left corner at `x`, `y`, every font pixel `scale`
+// canvas pixels big, cut off at `clip`
+pub(crate) fn draw_text(canvas: &mut Canvas<'_>, x: i32, y: i32, scale: i32, text: &str, color: u32, clip: Rect) {
+    let width = canvas.width as i32;
+    let clip = clip.intersect(&Rect { x: 0, y: 0, width, height: canvas.height as i32 });
+    for (j, c) in text.chars().enumerate() {
+        let x = x + j as i32 * ADVANCE * scale;
+        for (gy, bits) in glyph(c).iter().enumerate() {
+            for gx in 0..GLYPH_W {
+                if bits & (1 << (GLYPH_W - 1 - gx)) == 0 {
+                    continue;
+                }
+                let cell = Rect {
+                    x: x + gx * scale,
+                    y: y + gy as i32 * scale,
+                    width: scale,
+                    height: scale,
+                }
+                .intersect(&clip);
+                for py in cell.y..cell.y + cell.height {
+                    let start = (py * width + cell.x) as usize;
+                    canvas.pixels[start..start + cell.width as usize].fill(color);
+                }
+            }
+        }
+    }
+}
+
+// Lowercase shows as uppercase, anything else unknown as `?`
+fn glyph(c: char) -> &'static [u8; GLYPH_H as usize] {
+    let c = c.to_ascii_uppercase() as u32;
+    match c {
+        0x20..=0x5F => &GLYPHS[(c - 0x20) as usize],
+        _ => &GLYPHS[('?' as u32 - 0x20) as usize],
+    }
+}
+
+// Printable ASCII from space to underscore, one byte per row, low 5 bits
+#[rustfmt::skip]
+const GLYPHS: [[u8; GLYPH_H as usize]; 64] = [
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00], // space
+    [0x04, 0x04, 0x04, 0x04, 0x04, 0x00, 0x04], // !
+    [0x0A, 0x0A, 0x0A, 0x00, 0x00, 0x00, 0x00], // "
+    [0x0A, 0x0A, 0x1F, 0x0A, 0x1F, 0x0A, 0x0A], // #
+    [0x04, 0x0F, 0x14, 0x0E, 0x05, 0x1E, 0x04], // $
+    [0x18, 0x19, 0x02, 0x04, 0x08, 0x13, 0x03], // %
+    [0x0C, 0x12, 0x14, 0x08, 0x15, 0x12, 0x0D], // &
+    [0x0C, 0x04, 0x08, 0x00, 0x00, 0x00, 0x00], // '
+    [0x02, 0x04, 0x08, 0x08, 0x08, 0x04, 0x02], // (
+    [0x08, 0x04, 0x02, 0x02, 0x02, 0x04, 0x08], // )
+    [0x00, 0x04, 0x15, 0x0E, 0x15, 0x04, 0x00], // *
+    [0x00, 0x04, 0x04, 0x1F, 0x04, 0x04, 0x00], // +
+    [0x00, 0x00, 0x00, 0x00, 0x0C, 0x04, 0x08], // ,
+    [0x00, 0x00, 0x00, 0x1F, 0x00, 0x00, 0x00], // -
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x0C, 0x0C], // .
+    [0x00, 0x01, 0x02, 0x04, 0x08, 0x10, 0x00], // /
+    [0x0E, 0x11, 0x13, 0x15, 0x19, 0x11, 0x0E], // 0
+    [0x04, 0x0C, 0x04, 0x04, 0x04, 0x04, 0x0E], // 1
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x08, 0x1F], // 2
+    [0x1F, 0x02, 0x04, 0x02, 0x01, 0x11, 0x0E], // 3
+    [0x02, 0x06, 0x0A, 0x12, 0x1F, 0x02, 0x02], // 4
+    [0x1F, 0x10, 0x1E, 0x01, 0x01, 0x11, 0x0E], // 5
+    [0x06, 0x08, 0x10, 0x1E, 0x11, 0x11, 0x0E], // 6
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x08, 0x08], // 7
+    [0x0E, 0x11, 0x11, 0x0E, 0x11, 0x11, 0x0E], // 8
+    [0x0E, 0x11, 0x11, 0x0F, 0x01, 0x02, 0x0C], // 9
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x0C, 0x00], // :
+    [0x00, 0x0C, 0x0C, 0x00, 0x0C, 0x04, 0x08], // ;
+    [0x02, 0x04, 0x08, 0x10, 0x08, 0x04, 0x02], // <
+    [0x00, 0x00, 0x1F, 0x00, 0x1F, 0x00, 0x00], // =
+    [0x08, 0x04, 0x02, 0x01, 0x02, 0x04, 0x08], // >
+    [0x0E, 0x11, 0x01, 0x02, 0x04, 0x00, 0x04], // ?
+    [0x0E, 0x11, 0x01, 0x0D, 0x15, 0x15, 0x0E], // @
+    [0x0E, 0x11, 0x11, 0x11, 0x1F, 0x11, 0x11], // A
+    [0x1E, 0x11, 0x11, 0x1E, 0x11, 0x11, 0x1E], // B
+    [0x0E, 0x11, 0x10, 0x10, 0x10, 0x11, 0x0E], // C
+    [0x1C, 0x12, 0x11, 0x11, 0x11, 0x12, 0x1C], // D
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x1F], // E
+    [0x1F, 0x10, 0x10, 0x1E, 0x10, 0x10, 0x10], // F
+    [0x0E, 0x11, 0x10, 0x17, 0x11, 0x11, 0x0F], // G
+    [0x11, 0x11, 0x11, 0x1F, 0x11, 0x11, 0x11], // H
+    [0x0E, 0x04, 0x04, 0x04, 0x04, 0x04, 0x0E], // I
+    [0x07, 0x02, 0x02, 0x02, 0x02, 0x12, 0x0C], // J
+    [0x11, 0x12, 0x14, 0x18, 0x14, 0x12, 0x11], // K
+    [0x10, 0x10, 0x10, 0x10, 0x10, 0x10, 0x1F], // L
+    [0x11, 0x1B, 0x15, 0x15, 0x11, 0x11, 0x11], // M
+    [0x11, 0x11, 0x19, 0x15, 0x13, 0x11, 0x11], // N
+    [0x0E, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // O
+    [0x1E, 0x11, 0x11, 0x1E, 0x10, 0x10, 0x10], // P
+    [0x0E, 0x11, 0x11, 0x11, 0x15, 0x12, 0x0D], // Q
+    [0x1E, 0x11, 0x11, 0x1E, 0x14, 0x12, 0x11], // R
+    [0x0F, 0x10, 0x10, 0x0E, 0x01, 0x01, 0x1E], // S
+    [0x1F, 0x04, 0x04, 0x04, 0x04, 0x04, 0x04], // T
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x11, 0x0E], // U
+    [0x11, 0x11, 0x11, 0x11, 0x11, 0x0A, 0x04], // V
+    [0x11, 0x11, 0x11, 0x15, 0x15, 0x15, 0x0A], // W
+    [0x11, 0x11, 0x0A, 0x04, 0x0A, 0x11, 0x11], // X
+    [0x11, 0x11, 0x11, 0x0A, 0x04, 0x04, 0x04], // Y
+    [0x1F, 0x01, 0x02, 0x04, 0x08, 0x10, 0x1F], // Z
+    [0x0E, 0x08, 0x08, 0x08, 0x08, 0x08, 0x0E], // [
+    [0x00, 0x10, 0x08, 0x04, 0x02, 0x01, 0x00], // \
+    [0x0E, 0x02, 0x02, 0x02, 0x02, 0x02, 0x0E], // ]
+    [0x04, 0x0A, 0x11, 0x00, 0x00, 0x00, 0x00], // ^
+    [0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x1F], // _
+];
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCREEN: Rect = Rect { x: 0, y: 0, width: 640, height: 320 };
+
+    #[test]
+    fn toasts_expire() {
+        let mut osd = Osd::new(false);
+        let now = Instant::now();
+        assert!(!osd.update(now, Vec::new()));
+        osd.toast("Reset");
+        assert!(osd.update(now, Vec::new()));
+        assert!(!osd.update(now, Vec::new()));
+        assert!(osd.update(now + TOAST_TIME * 2, Vec::new()));
+        assert!(osd.area(SCREEN).is_none());
+    }
+
+    #[test]
+    fn damage_covers_old_and_new_text() {
+        let mut osd = Osd::new(false);
+        osd.update(Instant::now(), vec!["PAUSED".into()]);
+        let area = osd.area(SCREEN).unwrap();
+        assert_eq!(area, Rect { x: 2, y: 2, width: 6 * 6 + 2, height: LINE + 2 });
+        assert_eq!(osd.damage(SCREEN), [area]);
+        osd.update(Instant::now(), Vec::new());
+        assert_eq!(osd.damage(SCREEN), [area]);
+        assert!(osd.damage(SCREEN).is_empty());
+    }
+
+    #[test]
+    fn draws_inside_its_area() {
+        let mut osd = Osd::new(false);
+        osd.update(Instant::now(), vec!["I".into()]);
+        let mut pixels = vec![0x808080; 40 * 20];
+        let mut canvas = Canvas { pixels: &mut pixels, width: 40, height: 20 };
+        let bounds = Rect { x: 0, y: 0, width: 40, height: 20 };
+        osd.draw(&mut canvas, bounds);
+        let area = osd.area(bounds).unwrap();
+        for (i, pix) in pixels.iter().enumerate() {
+            let (x, y) = ((i % 40) as i32, (i / 40) as i32);
+            if !area.contains(x, y) {
+                assert_eq!(*pix, 0x808080);
+            }
+        }
+        // top bar of the I
+        let top = (area.y + PADDING) * 40 + area.x + PADDING;
+        assert_eq!(pixels[top as usize + 1..top as usize + 4], [TEXT; 3]);
+        assert_eq!(pixels[top as usize], filter::shade(0x808080, BACKDROP));
+    }
+}
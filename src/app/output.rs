@@ -0,0 +1,35 @@
+use super::*;
+
+#[derive(Debug)]
+pub(crate) struct Output {
+    pub wl_output: wl_output::WlOutput,
+    /// Registry name, needed to match `global_remove`
+    pub global: u32,
+    pub version: u32,
+    /// Connector name like `DP-1`, only sent from version 4 on
+    pub name: Option<String>,
+}
+
+impl App {
+    #[rustfmt::skip]
+    pub(crate) fn on_wloutput(&mut self, conn: &Connection, event: WlEvent<'_>) {
+        let id = event.header.id;
+        let Some(output) = self.outputs.iter_mut().find(|o| o.wl_output.id() == id) else {
+            return;
+        };
+        // geometry, modes and scale don't matter for picking one
+        if let wl_output::Event::Name { name } = output.wl_output.parse_event(conn.reader(), event) {
+            output.name = Some(name.to_string());
+        }
+    }
+
+    // Output picked with `--output`, `None` lets the compositor choose
+    pub(crate) fn fullscreen_output(&self) -> Option<&wl_output::WlOutput> {
+        let wanted = self.fullscreen_output.as_deref()?;
+        let output = self.outputs.iter().find(|o| o.name.as_deref() == Some(wanted));
+        if output.is_none() {
+            log!(WARNING, "No output named '{}', letting the compositor pick", wanted);
+        }
+        output.map(|o| &o.wl_output)
+    }
+}
@@ -0,0 +1,78 @@
+// Xrgb8888 colours indexed by pixel value: background, plane 1, plane 2 and
+// both planes. Plain CHIP-8 only ever uses the first two.
+pub type Palette = [u32; 4];
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Theme {
+    #[default]
+    Green,
+    Amber,
+    White,
+    GameBoy,
+    HighContrast,
+}
+
+impl Theme {
+    pub const ALL: &[Theme] = &[Self::Green, Self::Amber, Self::White, Self::GameBoy, Self::HighContrast];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Green => "green",
+            Self::Amber => "amber",
+            Self::White => "white",
+            Self::GameBoy => "gameboy",
+            Self::HighContrast => "high-contrast",
+        }
+    }
+
+    pub fn palette(&self) -> Palette {
+        match self {
+            Self::Green => [0x000000, 0x79b67b, 0x3d5b3e, 0xc6f0c7],
+            Self::Amber => [0x000000, 0xffb000, 0x805800, 0xffe2a0],
+            Self::White => [0x000000, 0xffffff, 0x808080, 0xc0c0c0],
+            Self::GameBoy => [0x0f380f, 0x9bbc0f, 0x306230, 0x8bac0f],
+            Self::HighContrast => [0x000000, 0xffff00, 0x00ffff, 0xffffff],
+        }
+    }
+}
+
+// Two (background, foreground) or four colours as `rrggbb`, with or without
+// a leading `#`, separated by commas or whitespace
+pub fn parse(s: &str) -> Result<Palette, String> {
+    let colors = s
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|c| !c.is_empty())
+        .map(|c| {
+            let hex = c.strip_prefix('#').unwrap_or(c);
+            match hex.len() {
+                6 => u32::from_str_radix(hex, 16).ok(),
+                _ => None,
+            }
+            .ok_or_else(|| format!("'{}' is not a rrggbb colour", c))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+    match colors[..] {
+        [bg, fg] => Ok([bg, fg, fg, fg]),
+        [bg, p1, p2, both] => Ok([bg, p1, p2, both]),
+        _ => Err(format!("expected 2 or 4 colours, got {}", colors.len())),
+    }
+}
+
+pub fn load(path: &str) -> std::io::Result<Palette> {
+    let data = std::fs::read_to_string(path)?;
+    parse(&data).map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidData, e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_colours() {
+        assert_eq!(parse("000000,79b67b"), Ok([0, 0x79b67b, 0x79b67b, 0x79b67b]));
+        assert_eq!(parse("#102030 #405060\n#708090 #a0b0c0\n"), Ok([0x102030, 0x405060, 0x708090, 0xa0b0c0]));
+        assert!(parse("000000").is_err());
+        assert!(parse("000000,12345").is_err());
+        assert!(parse("000000,gggggg").is_err());
+    }
+}
@@ -0,0 +1,163 @@
+// The picture at native resolution on a subsurface of its own, stretched by
+// the compositor onto the letterboxed rect. The window underneath shows a
+// single background pixel stretched over all of it. Used whenever only the
+// picture is on screen at a whole scale, anything drawn at window resolution
+// (title bar, keypad, on-screen text, filters) goes back to drawing the whole
+// window. So does a fractional scale, a logical rect can't put CHIP-8 pixels
+// on whole device pixels there.
+use super::*;
+
+impl App {
+    // Creates the subsurface, `None` without wp_viewporter or wl_subcompositor
+    pub(crate) fn create_picture(
+        &self,
+        conn: &Connection,
+        parent: &wl_surface::WlSurface,
+    ) -> Option<(wl_surface::WlSurface, wl_subsurface::WlSubsurface, WpViewport)> {
+        let wl_compositor = self.wl_compositor.as_ref()?;
+        let subcompositor = self.wl_subcompositor.as_ref()?;
+        let viewporter = self.viewporter.as_ref()?;
+        let surface = wl_compositor.create_surface(conn.writer());
+        let subsurface = subcompositor.get_subsurface(conn.writer(), &surface, parent);
+        let viewport = viewporter.get_viewport(conn.writer(), &surface);
+        // pointer, touch and drops all go to the window underneath
+        let region = wl_compositor.create_region(conn.writer());
+        surface.set_input_region(conn.writer(), Some(&region));
+        region.destroy(conn.writer());
+        Some((surface, subsurface, viewport))
+    }
+
+    // Moves the subsurface onto the picture's rect in the window, the
+    // background comes along when it wasn't showing. Both take effect with
+    // the next commit of the window.
+    pub(crate) fn show_picture(&mut self, conn: &Connection) {
+        let (Some(subsurface), Some(viewport)) = (self.picture_subsurface.as_ref(), self.picture_viewport.as_ref())
+        else {
+            return;
+        };
+        let Some(dst) = self.native_rect() else {
+            return;
+        };
+        let shown = self.picture_dst.replace(dst);
+        if shown != Some(dst) {
+            subsurface.set_position(conn.writer(), dst.x, dst.y);
+            viewport.set_destination(conn.writer(), dst.width, dst.height);
+        }
+        if shown.is_none() {
+            self.draw_background(conn);
+        }
+    }
+
+    // Where the picture surface goes in the window, `None` when it can't
+    // show the picture as crisply as drawing the whole window would
+    pub(crate) fn native_rect(&self) -> Option<render::Rect> {
+        // native only happens without title bar or keypad, so it's the window
+        native_rect(self.scaling, self.win_width, self.win_height, self.scale120())
+    }
+
+    // Takes the picture's buffer away, the window draws everything again
+    pub(crate) fn hide_picture(&mut self, conn: &Connection) {
+        if self.picture_dst.take().is_none() {
+            return;
+        }
+        if let Some(surface) = self.picture_surface.as_ref() {
+            surface.attach(conn.writer(), None, 0, 0);
+            surface.commit(conn.writer());
+        }
+        // a hidden surface gets no more frames, the window asks for its own
+        self.frame_callback = None;
+    }
+
+    // One pixel of the background colour, the window's viewport stretches
+    // it over the letterbox
+    fn draw_background(&mut self, conn: &Connection) {
+        if self.background.is_none() {
+            let Some(wl_shm) = self.wl_shm.as_ref() else {
+                return;
+            };
+            match buffers::BufferPool::new(conn, wl_shm, 1, 1) {
+                Ok(pool) => {
+                    for id in pool.ids().collect::<Vec<_>>() {
+                        self.add_callback(id, Self::on_wlbuffer);
+                    }
+                    self.background = Some(pool);
+                }
+                Err(e) => {
+                    log!(ERR, "Couldn't allocate the background: {}", e);
+                    return;
+                }
+            }
+        }
+        let (Some(background), Some(wl_surface)) = (self.background.as_mut(), self.base_surface.as_ref()) else {
+            return;
+        };
+        let Some(index) = background.acquire() else {
+            return;
+        };
+        background.pixels(index)[0] = self.palettes[self.palette][0];
+        background.attach(conn, wl_surface, index);
+        wl_surface.damage_buffer(conn.writer(), 0, 0, 1, 1);
+    }
+}
+
+// The letterbox in logical pixels for a `width`x`height` window at `scale120`.
+// It's worked out in device pixels like the software path does. Integer
+// scaling keeps to multiples whose size and offset divide into whole
+// logical pixels, so every CHIP-8 pixel still covers the same whole number
+// of device pixels once the compositor scales it back up.
+fn native_rect(scaling: render::Scaling, width: i32, height: i32, scale120: u32) -> Option<render::Rect> {
+    if !scale120.is_multiple_of(scale::SCALE_ONE) {
+        return None;
+    }
+    let scale = (scale120 / scale::SCALE_ONE) as i32;
+    let (w, h) = (width * scale, height * scale);
+    let (src_w, src_h) = (chip8::WIDTH as i32, chip8::HEIGHT as i32);
+    let mut device = render::fit(scaling, w, h, chip8::WIDTH, chip8::HEIGHT);
+    if scaling == render::Scaling::Integer {
+        // smallest multiple whose sides divide by the scale
+        let step = (1..=scale).find(|k| src_w * k % scale == 0 && src_h * k % scale == 0)?;
+        let multiple = device.width / src_w / step * step;
+        if multiple == 0 {
+            return None;
+        }
+        device.width = src_w * multiple;
+        device.height = src_h * multiple;
+        device.x = (w - device.width) / 2;
+        device.y = (h - device.height) / 2;
+    }
+    Some(render::Rect {
+        x: device.x / scale,
+        y: device.y / scale,
+        width: (device.width / scale).max(1),
+        height: (device.height / scale).max(1),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use render::{Rect, Scaling};
+
+    #[test]
+    fn whole_scales_only() {
+        assert_eq!(native_rect(Scaling::Integer, 700, 400, 120), Some(Rect { x: 30, y: 40, width: 640, height: 320 }));
+        assert_eq!(native_rect(Scaling::Integer, 700, 400, 180), None);
+        assert_eq!(native_rect(Scaling::Fractional, 700, 400, 150), None);
+        assert_eq!(native_rect(Scaling::Fractional, 700, 400, 240), Some(Rect { x: 0, y: 25, width: 700, height: 350 }));
+    }
+
+    #[test]
+    fn integer_multiples_land_on_whole_device_pixels() {
+        // 2100x1200 device pixels fit 32x, 30x is the largest that 3 divides
+        let rect = native_rect(Scaling::Integer, 700, 400, 360).unwrap();
+        assert_eq!(rect, Rect { x: 30, y: 40, width: 640, height: 320 });
+        for (width, height, scale) in [(700, 400, 240), (333, 222, 360), (1021, 767, 480)] {
+            let rect = native_rect(Scaling::Integer, width, height, scale).unwrap();
+            let device = rect.width * scale as i32 / 120;
+            assert_eq!(device % chip8::WIDTH as i32, 0, "{}x{} at {}", width, height, scale);
+            assert_eq!(rect.width, rect.height * 2);
+        }
+        // 180x120 device pixels fit 2x, 3x doesn't
+        assert_eq!(native_rect(Scaling::Integer, 60, 40, 360), None);
+    }
+}
@@ -0,0 +1,299 @@
+// Just enough PNG for screenshots: 8-bit RGB, one IDAT, deflate with the
+// fixed Huffman codes and nothing but runs. Scaled up CHIP-8 pixels are
+// long runs along a row and repeated rows, so every row is filtered with
+// Up when it matches the one above (all zeros) and Sub otherwise (zeros
+// wherever a colour goes on), which leaves mostly runs of zeros.
+
+const SIGNATURE: [u8; 8] = [0x89, b'P', b'N', b'G', b'\r', b'\n', 0x1A, b'\n'];
+/// Longest deflate match
+const MAX_RUN: usize = 258;
+
+// Xrgb8888 `pixels`, `width` per row
+pub fn encode(pixels: &[u32], width: usize, height: usize) -> Vec<u8> {
+    let stride = width * 3;
+    let mut raw = Vec::with_capacity((stride + 1) * height);
+    let mut prev: Option<&[u32]> = None;
+    for row in pixels.chunks_exact(width).take(height) {
+        let rgb: Vec<u8> = row.iter().flat_map(|p| [(p >> 16) as u8, (p >> 8) as u8, *p as u8]).collect();
+        match prev == Some(row) {
+            true => {
+                raw.push(2);
+                raw.resize(raw.len() + stride, 0);
+            }
+            false => {
+                raw.push(1);
+                raw.extend(rgb.iter().enumerate().map(|(i, b)| match i {
+                    0..3 => *b,
+                    _ => b.wrapping_sub(rgb[i - 3]),
+                }));
+            }
+        }
+        prev = Some(row);
+    }
+
+    let mut ihdr = Vec::with_capacity(13);
+    ihdr.extend((width as u32).to_be_bytes());
+    ihdr.extend((height as u32).to_be_bytes());
+    // bit depth, truecolour, deflate, adaptive filters, no interlacing
+    ihdr.extend([8, 2, 0, 0, 0]);
+
+    let mut png = SIGNATURE.to_vec();
+    chunk(&mut png, b"IHDR", &ihdr);
+    chunk(&mut png, b"IDAT", &zlib(&raw));
+    chunk(&mut png, b"IEND", &[]);
+    png
+}
+
+fn chunk(png: &mut Vec<u8>, kind: &[u8; 4], data: &[u8]) {
+    png.extend((data.len() as u32).to_be_bytes());
+    let start = png.len();
+    png.extend(kind);
+    png.extend(data);
+    let crc = crc32(&png[start..]);
+    png.extend(crc.to_be_bytes());
+}
+
+fn zlib(data: &[u8]) -> Vec<u8> {
+    // deflate, 32K window, no dictionary
+    let mut out = BitWriter { bytes: vec![0x78, 0x01], bits: 0, count: 0 };
+    // the only block, fixed codes
+    out.put(1, 1);
+    out.put(1, 2);
+    let mut i = 0;
+    while i < data.len() {
+        literal(&mut out, data[i]);
+        let run = data[i + 1..].iter().take_while(|b| **b == data[i]).count();
+        i += 1 + run;
+        // matches one back repeat the literal
+        let mut left = run;
+        while left >= 3 {
+            let len = left.min(MAX_RUN);
+            // a 1 or 2 byte tail can't be a match
+            let len = if left - len > 0 && left - len < 3 { len - 3 } else { len };
+            length(&mut out, len);
+            // distance 1: code 0, no extra bits
+            out.put_code(0, 5);
+            left -= len;
+        }
+        for _ in 0..left {
+            literal(&mut out, data[i - 1]);
+        }
+    }
+    out.put_code(0, 7); // end of block
+    let mut bytes = out.finish();
+    bytes.extend(adler32(data).to_be_bytes());
+    bytes
+}
+
+fn literal(out: &mut BitWriter, byte: u8) {
+    match byte {
+        0..=143 => out.put_code(0x30 + byte as u32, 8),
+        _ => out.put_code(0x190 + (byte as u32 - 144), 9),
+    }
+}
+
+// Length code and extra bits for a 3-258 byte match
+fn length(out: &mut BitWriter, len: usize) {
+    const BASE: [usize; 29] = [
+        3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195,
+        227, 258,
+    ];
+    let i = BASE.iter().rposition(|b| *b <= len).unwrap_or(0);
+    let extra = match i {
+        8..28 => (i - 4) / 4,
+        _ => 0,
+    };
+    let code = 257 + i as u32;
+    match code {
+        ..280 => out.put_code(code - 256, 7),
+        _ => out.put_code(0xC0 + code - 280, 8),
+    }
+    out.put((len - BASE[i]) as u32, extra as u32);
+}
+
+struct BitWriter {
+    bytes: Vec<u8>,
+    bits: u32,
+    count: u32,
+}
+
+impl BitWriter {
+    // Plain values go in least significant bit first
+    fn put(&mut self, value: u32, count: u32) {
+        self.bits |= value << self.count;
+        self.count += count;
+        while self.count >= 8 {
+            self.bytes.push(self.bits as u8);
+            self.bits >>= 8;
+            self.count -= 8;
+        }
+    }
+
+    // Huffman codes the other way around
+    fn put_code(&mut self, code: u32, count: u32) {
+        self.put(code.reverse_bits() >> (32 - count), count);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        if self.count > 0 {
+            self.bytes.push(self.bits as u8);
+        }
+        self.bytes
+    }
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let table: Vec<u32> = (0..256u32)
+        .map(|n| (0..8).fold(n, |c, _| if c & 1 != 0 { 0xEDB88320 ^ (c >> 1) } else { c >> 1 }))
+        .collect();
+    !data.iter().fold(!0u32, |c, b| table[((c ^ *b as u32) & 0xFF) as usize] ^ (c >> 8))
+}
+
+fn adler32(data: &[u8]) -> u32 {
+    let (a, b) = data.iter().fold((1u32, 0u32), |(a, b), byte| {
+        let a = (a + *byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    (b << 16) | a
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn checksums() {
+        assert_eq!(crc32(b"IEND"), 0xAE426082);
+        assert_eq!(adler32(b"Wikipedia"), 0x11E60398);
+    }
+
+    #[test]
+    fn layout() {
+        let png = encode(&[0xFF0000, 0x00FF00, 0x0000FF, 0xFFFFFF], 2, 2);
+        assert_eq!(png[..8], SIGNATURE);
+        assert_eq!(&png[12..16], b"IHDR");
+        assert_eq!(png[16..24], [0, 0, 0, 2, 0, 0, 0, 2]);
+        assert_eq!(png[png.len() - 12..], [0, 0, 0, 0, b'I', b'E', b'N', b'D', 0xAE, 0x42, 0x60, 0x82]);
+    }
+
+    // Just the fixed Huffman half of inflate, which is all `zlib` writes
+    fn inflate(zlib: &[u8]) -> Vec<u8> {
+        const LEN_BASE: [usize; 29] = [
+            3, 4, 5, 6, 7, 8, 9, 10, 11, 13, 15, 17, 19, 23, 27, 31, 35, 43, 51, 59, 67, 83, 99, 115, 131, 163, 195,
+            227, 258,
+        ];
+        const DIST_BASE: [usize; 30] = [
+            1, 2, 3, 4, 5, 7, 9, 13, 17, 25, 33, 49, 65, 97, 129, 193, 257, 385, 513, 769, 1025, 1537, 2049, 3073,
+            4097, 6145, 8193, 12289, 16385, 24577,
+        ];
+        assert_eq!(zlib[0] & 0x0F, 8, "not deflate");
+        assert_eq!(u16::from_be_bytes([zlib[0], zlib[1]]) % 31, 0, "bad header check");
+        let data = &zlib[2..];
+        let mut pos = 0;
+        // plain values come least significant bit first
+        let mut bits = |count: u32| {
+            let v = (0..count).fold(0, |v, i| {
+                let bit = (data[(pos + i as usize) / 8] >> ((pos + i as usize) % 8)) & 1;
+                v | (bit as usize) << i
+            });
+            pos += count as usize;
+            v
+        };
+        assert_eq!(bits(1), 1, "only block");
+        assert_eq!(bits(2), 1, "fixed codes");
+        let mut out: Vec<u8> = Vec::new();
+        loop {
+            // codes come most significant bit first, 7 to 9 of them
+            let mut code = (0..7).fold(0, |c, _| c << 1 | bits(1));
+            let sym = match code {
+                0x00..=0x17 => 256 + code,
+                _ => {
+                    code = code << 1 | bits(1);
+                    match code {
+                        0x30..=0xBF => code - 0x30,
+                        0xC0..=0xC7 => 280 + code - 0xC0,
+                        _ => 144 + (code << 1 | bits(1)) - 0x190,
+                    }
+                }
+            };
+            match sym {
+                0..256 => out.push(sym as u8),
+                256 => break,
+                _ => {
+                    let i = sym - 257;
+                    let extra = match i {
+                        8..28 => (i - 4) / 4,
+                        _ => 0,
+                    };
+                    let len = LEN_BASE[i] + bits(extra as u32);
+                    let d = (0..5).fold(0, |c, _| c << 1 | bits(1));
+                    let extra = if d < 4 { 0 } else { (d - 2) / 2 };
+                    let dist = DIST_BASE[d] + bits(extra as u32);
+                    for _ in 0..len {
+                        out.push(out[out.len() - dist]);
+                    }
+                }
+            }
+        }
+        let end = pos.div_ceil(8);
+        assert_eq!(data[end..end + 4], adler32(&out).to_be_bytes());
+        out
+    }
+
+    #[test]
+    fn round_trip() {
+        // repeated rows, runs past a match's 258 bytes, 8 and 9 bit literals
+        let (width, height) = (200, 6);
+        let mut pixels = vec![0x101010; width * height];
+        pixels[2 * width..4 * width].fill(0xFFFFFF);
+        for (x, p) in pixels[4 * width..5 * width].iter_mut().enumerate() {
+            *p = (x as u32 * 0x010307) & 0xFFFFFF;
+        }
+        pixels[5 * width + 7] = 0x8090A0;
+        let png = encode(&pixels, width, height);
+
+        let mut chunks = Vec::new();
+        let mut rest = &png[8..];
+        while !rest.is_empty() {
+            let len = u32::from_be_bytes(rest[..4].try_into().unwrap()) as usize;
+            let (kind, data) = (&rest[4..8], &rest[8..8 + len]);
+            assert_eq!(rest[8 + len..12 + len], crc32(&rest[4..8 + len]).to_be_bytes());
+            chunks.push((kind, data));
+            rest = &rest[12 + len..];
+        }
+        let idat = chunks.iter().find(|(kind, _)| *kind == b"IDAT").unwrap().1;
+        let raw = inflate(idat);
+
+        // undo the filters and get the same pixels back
+        let stride = width * 3;
+        assert_eq!(raw.len(), (stride + 1) * height);
+        let mut prev = vec![0u8; stride];
+        let mut filters = Vec::new();
+        for (y, line) in raw.chunks_exact(stride + 1).enumerate() {
+            let mut rgb = line[1..].to_vec();
+            match line[0] {
+                1 => (3..stride).for_each(|i| rgb[i] = rgb[i].wrapping_add(rgb[i - 3])),
+                2 => rgb.iter_mut().zip(&prev).for_each(|(b, p)| *b = b.wrapping_add(*p)),
+                f => panic!("filter {}", f),
+            }
+            let expected: Vec<u8> =
+                pixels[y * width..][..width].iter().flat_map(|p| [(p >> 16) as u8, (p >> 8) as u8, *p as u8]).collect();
+            assert_eq!(rgb, expected, "row {}", y);
+            filters.push(line[0]);
+            prev = rgb;
+        }
+        // Up for rows like the one above, Sub otherwise
+        assert_eq!(filters, [1, 2, 1, 2, 1, 1]);
+    }
+
+    #[test]
+    fn runs_compress() {
+        // a 10x scaled screen with one lit pixel
+        let mut pixels = vec![0x101010; 640 * 320];
+        for y in 0..10 {
+            pixels[y * 640..y * 640 + 10].fill(0xFFFFFF);
+        }
+        // under a hundredth of the pixel data
+        assert!(encode(&pixels, 640, 320).len() < 640 * 320 * 3 / 100);
+    }
+}
@@ -0,0 +1,90 @@
+use super::*;
+
+/// evdev code of the left mouse button
+const BTN_LEFT: u32 = 0x110;
+
+impl App {
+    #[rustfmt::skip]
+    pub(crate) fn on_wlpointer(&mut self, conn: &Connection, event: WlEvent<'_>) {
+        let Some(wl_pointer) = self.wl_pointer.as_ref() else {
+            return;
+        };
+        match wl_pointer.parse_event(conn.reader(), event) {
+            wl_pointer::Event::Enter { serial, surface, surface_x, surface_y } => {
+                self.pointer_pos = (surface_x as i32, surface_y as i32);
+                let (x, y) = self.to_buffer(surface_x, surface_y);
+                self.update_keypad(|k| k.pointer_motion(x, y));
+            },
+            wl_pointer::Event::Leave { .. } => self.update_keypad(|k| k.pointer_leave()),
+            wl_pointer::Event::Motion { time, surface_x, surface_y } => {
+                self.pointer_pos = (surface_x as i32, surface_y as i32);
+                let (x, y) = self.to_buffer(surface_x, surface_y);
+                self.update_keypad(|k| k.pointer_motion(x, y));
+            },
+            wl_pointer::Event::Button { serial, time, button: BTN_LEFT, state } => {
+                let pressed = state == wl_pointer::ButtonState::Pressed;
+                if pressed && self.csd_click(conn, serial) {
+                    return;
+                }
+                self.update_keypad(|k| k.pointer_button(pressed));
+            },
+            _ => {}
+        }
+    }
+
+    #[rustfmt::skip]
+    pub(crate) fn on_wltouch(&mut self, conn: &Connection, event: WlEvent<'_>) {
+        let Some(wl_touch) = self.wl_touch.as_ref() else {
+            return;
+        };
+        match wl_touch.parse_event(conn.reader(), event) {
+            wl_touch::Event::Down { serial, time, surface, id, x, y } => {
+                let (x, y) = self.to_buffer(x, y);
+                self.update_keypad(|k| k.touch_down(id, x, y));
+            },
+            wl_touch::Event::Up { serial, time, id } => self.update_keypad(|k| k.touch_up(id)),
+            wl_touch::Event::Motion { time, id, x, y } => {
+                let (x, y) = self.to_buffer(x, y);
+                self.update_keypad(|k| k.touch_motion(id, x, y));
+            },
+            wl_touch::Event::Cancel => self.update_keypad(|k| k.touch_cancel()),
+            _ => {}
+        }
+    }
+
+    // Applies whatever the mouse or touches changed on the keypad to the
+    // emulated keys
+    fn update_keypad(&mut self, f: impl FnOnce(&mut keypad::Keypad)) {
+        let Some(keypad) = self.keypad.as_mut() else {
+            return;
+        };
+        f(keypad);
+        self.sync_keys();
+    }
+
+    // A fresh press or a release of a keyboard key bound to `key`
+    pub(crate) fn set_key(&mut self, key: u8, pressed: bool) {
+        let count = &mut self.keyboard_keys[key as usize];
+        *count = match pressed {
+            true => count.saturating_add(1),
+            false => count.saturating_sub(1),
+        };
+        self.sync_keys();
+    }
+
+    // A CHIP-8 key is down while anything holds it, letting go of one
+    // doesn't release it under the others
+    pub(crate) fn sync_keys(&mut self) {
+        let held = self.keypad.as_ref().map_or([false; 16], |k| k.held());
+        let mut changed = false;
+        for (key, down) in self.chip8.keys.iter_mut().enumerate() {
+            let now = self.keyboard_keys[key] > 0 || held[key];
+            changed |= *down != now;
+            *down = now;
+        }
+        // key changes from the keyboard show on the keypad too
+        if changed && self.keypad.is_some() {
+            self.chip8.need_redraw = true;
+        }
+    }
+}
@@ -0,0 +1,406 @@
+// Video capture of the emulated screen at a steady 60 fps. Frames come in
+// as the composed colours (palette and persistence, no filters) with how
+// many video frames they stay up for, pictures that don't change are just
+// repeated. Y4M is raw 4:4:4 video any encoder reads, GIF is for dropping
+// straight into a bug report.
+use std::collections::HashMap;
+use std::io::Write;
+
+use super::chip8::{HEIGHT, WIDTH};
+
+pub const FPS: u64 = 60;
+/// Video pixels per CHIP-8 pixel
+const SCALE: usize = 4;
+/// Shortest GIF frame delay in hundredths of a second, viewers slow
+/// anything shorter down to a crawl
+const MIN_DELAY: u64 = 2;
+/// GIF LZW codes top out at 12 bits
+const MAX_CODE: u32 = (1 << 12) - 1;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    #[default]
+    Gif,
+    Y4m,
+}
+
+impl Format {
+    pub const ALL: &[Format] = &[Self::Gif, Self::Y4m];
+
+    // Also the file extension
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Gif => "gif",
+            Self::Y4m => "y4m",
+        }
+    }
+
+    // By extension
+    pub fn from_path(path: &str) -> Result<Self, String> {
+        let ext = std::path::Path::new(path).extension().map(|e| e.to_string_lossy().to_lowercase());
+        Self::ALL
+            .iter()
+            .find(|f| ext.as_deref() == Some(f.name()))
+            .copied()
+            .ok_or_else(|| format!("'{}' is neither a .gif nor a .y4m", path))
+    }
+}
+
+#[derive(Debug)]
+pub(crate) struct Recorder<W: Write = std::io::BufWriter<std::fs::File>> {
+    out: W,
+    format: Format,
+    /// Video frames so far
+    pub frames: u64,
+    /// GIF picture waiting to find out how long it stays up, and its
+    /// first video frame
+    pending: Option<(Vec<u32>, u64)>,
+    /// Last GIF picture written, later frames only cover what changed
+    shown: Option<Vec<u32>>,
+}
+
+impl<W: Write> Recorder<W> {
+    pub fn new(mut out: W, format: Format) -> std::io::Result<Self> {
+        let (width, height) = ((WIDTH * SCALE) as u16, (HEIGHT * SCALE) as u16);
+        match format {
+            Format::Y4m => writeln!(out, "YUV4MPEG2 W{} H{} F{}:1 Ip A1:1 C444", width, height, FPS)?,
+            Format::Gif => {
+                out.write_all(b"GIF89a")?;
+                // no global colour table, every frame brings its own
+                out.write_all(&width.to_le_bytes())?;
+                out.write_all(&height.to_le_bytes())?;
+                out.write_all(&[0, 0, 0])?;
+                // loop forever
+                out.write_all(b"\x21\xFF\x0BNETSCAPE2.0\x03\x01\x00\x00\x00")?;
+            }
+        }
+        Ok(Self { out, format, frames: 0, pending: None, shown: None })
+    }
+
+    // `colors` for the next `count` video frames
+    pub fn frame(&mut self, colors: &[u32], count: u64) -> std::io::Result<()> {
+        if count == 0 {
+            return Ok(());
+        }
+        match self.format {
+            Format::Y4m => {
+                let frame = yuv444(colors);
+                for _ in 0..count {
+                    self.out.write_all(b"FRAME\n")?;
+                    self.out.write_all(&frame)?;
+                }
+            }
+            Format::Gif => match self.pending.take() {
+                Some((pending, start)) if pending == colors => self.pending = Some((pending, start)),
+                // too short to show, the new picture takes its place
+                Some((_, start)) if delay(start, self.frames) < MIN_DELAY => self.pending = Some((colors.to_vec(), start)),
+                pending => {
+                    if let Some((pending, start)) = pending {
+                        self.gif_frame(&pending, start)?;
+                    }
+                    self.pending = Some((colors.to_vec(), self.frames));
+                }
+            },
+        }
+        self.frames += count;
+        Ok(())
+    }
+
+    pub fn finish(mut self) -> std::io::Result<W> {
+        if let Some((pending, start)) = self.pending.take() {
+            self.gif_frame(&pending, start)?;
+        }
+        if self.format == Format::Gif {
+            self.out.write_all(&[0x3B])?;
+        }
+        self.out.flush()?;
+        Ok(self.out)
+    }
+
+    // `colors` from video frame `start` up to now, only the part that
+    // differs from the last one
+    fn gif_frame(&mut self, colors: &[u32], start: u64) -> std::io::Result<()> {
+        let area = match self.shown.as_deref() {
+            Some(shown) => changed(shown, colors).unwrap_or((0, 0, 1, 1)),
+            None => (0, 0, WIDTH, HEIGHT),
+        };
+        let (x, y, width, height) = area;
+        let (table, indices) = palettize(colors, area);
+        // smallest power of two that fits, at least 2 bits for LZW
+        let bits = (usize::BITS - (table.len() - 1).leading_zeros()).max(2);
+
+        // graphic control: keep what's under the frame, delay
+        self.out.write_all(&[0x21, 0xF9, 0x04, 0x04])?;
+        self.out.write_all(&(delay(start, self.frames) as u16).to_le_bytes())?;
+        self.out.write_all(&[0, 0])?;
+        self.out.write_all(&[0x2C])?;
+        for v in [x * SCALE, y * SCALE, width * SCALE, height * SCALE] {
+            self.out.write_all(&(v as u16).to_le_bytes())?;
+        }
+        self.out.write_all(&[0x80 | (bits - 1) as u8])?;
+        for i in 0..1 << bits {
+            let color = table.get(i).copied().unwrap_or(0);
+            self.out.write_all(&[(color >> 16) as u8, (color >> 8) as u8, color as u8])?;
+        }
+
+        let scaled: Vec<u8> = indices
+            .chunks_exact(width)
+            .flat_map(|row| {
+                let row: Vec<u8> = row.iter().flat_map(|i| [*i; SCALE]).collect();
+                std::iter::repeat_n(row, SCALE).flatten()
+            })
+            .collect();
+        let data = lzw(&scaled, bits);
+        self.out.write_all(&[bits as u8])?;
+        for block in data.chunks(255) {
+            self.out.write_all(&[block.len() as u8])?;
+            self.out.write_all(block)?;
+        }
+        self.out.write_all(&[0])?;
+        self.shown = Some(colors.to_vec());
+        Ok(())
+    }
+}
+
+// Hundredths of a second between video frames `start` and `end`, rounded
+// the same way everywhere so the delays add up to the right length
+fn delay(start: u64, end: u64) -> u64 {
+    let cs = |frame: u64| (frame * 100 + FPS / 2) / FPS;
+    cs(end) - cs(start)
+}
+
+// Bounding box of the pixels that differ, None when nothing does
+fn changed(old: &[u32], new: &[u32]) -> Option<(usize, usize, usize, usize)> {
+    let diff = |i: usize| old[i] != new[i];
+    let rows: Vec<usize> = (0..HEIGHT).filter(|y| (0..WIDTH).any(|x| diff(y * WIDTH + x))).collect();
+    let columns: Vec<usize> = (0..WIDTH).filter(|x| (0..HEIGHT).any(|y| diff(y * WIDTH + x))).collect();
+    let (y0, y1) = (*rows.first()?, *rows.last()?);
+    let (x0, x1) = (*columns.first()?, *columns.last()?);
+    Some((x0, y0, x1 - x0 + 1, y1 - y0 + 1))
+}
+
+// Colour table and an index per pixel for `area` of `colors`. Fading
+// pixels can bring more than 256 colours, those lose low bits until they
+// fit.
+fn palettize(colors: &[u32], area: (usize, usize, usize, usize)) -> (Vec<u32>, Vec<u8>) {
+    let (x, y, width, height) = area;
+    let pixels: Vec<u32> = (y..y + height)
+        .flat_map(|row| colors[row * WIDTH + x..][..width].iter().copied())
+        .collect();
+    for drop in 0..8 {
+        let mask = (0xFF >> drop << drop) * 0x010101;
+        let mut table = Vec::new();
+        let mut index = HashMap::new();
+        let indices: Option<Vec<u8>> = pixels
+            .iter()
+            .map(|p| {
+                let color = p & mask;
+                let i = *index.entry(color).or_insert_with(|| {
+                    table.push(color);
+                    table.len() - 1
+                });
+                u8::try_from(i).ok()
+            })
+            .collect();
+        if let Some(indices) = indices {
+            return (table, indices);
+        }
+    }
+    unreachable!("8 colours left at most")
+}
+
+// GIF flavoured LZW, least significant bit first, with codes growing
+// from `bits` + 1 up to 12 bits and a clear code whenever they run out
+fn lzw(indices: &[u8], bits: u32) -> Vec<u8> {
+    let clear = 1u32 << bits;
+    let eoi = clear + 1;
+    let mut out = Vec::new();
+    let (mut acc, mut count) = (0u32, 0u32);
+    let mut width = bits + 1;
+    let mut write = |code: u32, width: u32| {
+        acc |= code << count;
+        count += width;
+        while count >= 8 {
+            out.push(acc as u8);
+            acc >>= 8;
+            count -= 8;
+        }
+    };
+
+    let mut table: HashMap<(u32, u8), u32> = HashMap::new();
+    let mut hi = eoi;
+    write(clear, width);
+    let Some((&first, rest)) = indices.split_first() else {
+        write(eoi, width);
+        return finish_bits(out, acc, count);
+    };
+    let mut prefix = first as u32;
+    for &k in rest {
+        if let Some(code) = table.get(&(prefix, k)) {
+            prefix = *code;
+            continue;
+        }
+        write(prefix, width);
+        let key = (prefix, k);
+        prefix = k as u32;
+        hi += 1;
+        if hi == 1 << width {
+            width += 1;
+        }
+        if hi == MAX_CODE {
+            write(clear, width);
+            table.clear();
+            hi = eoi;
+            width = bits + 1;
+            continue;
+        }
+        table.insert(key, hi);
+    }
+    write(prefix, width);
+    // the decoder adds an entry for the last code too
+    hi += 1;
+    if hi == 1 << width {
+        width += 1;
+    }
+    if hi == MAX_CODE {
+        write(clear, width);
+        width = bits + 1;
+    }
+    write(eoi, width);
+    finish_bits(out, acc, count)
+}
+
+fn finish_bits(mut out: Vec<u8>, acc: u32, count: u32) -> Vec<u8> {
+    if count > 0 {
+        out.push(acc as u8);
+    }
+    out
+}
+
+// Full range RGB to BT.601 studio range Y, U and V planes, scaled up
+fn yuv444(colors: &[u32]) -> Vec<u8> {
+    let (width, height) = (WIDTH * SCALE, HEIGHT * SCALE);
+    let mut planes = vec![0; width * height * 3];
+    let (y_plane, rest) = planes.split_at_mut(width * height);
+    let (u_plane, v_plane) = rest.split_at_mut(width * height);
+    for (i, color) in colors.iter().enumerate() {
+        let (r, g, b) = (((color >> 16) & 0xFF) as i32, ((color >> 8) & 0xFF) as i32, (color & 0xFF) as i32);
+        let y = ((66 * r + 129 * g + 25 * b + 128) >> 8) + 16;
+        let u = ((-38 * r - 74 * g + 112 * b + 128) >> 8) + 128;
+        let v = ((112 * r - 94 * g - 18 * b + 128) >> 8) + 128;
+        let (x0, y0) = (i % WIDTH * SCALE, i / WIDTH * SCALE);
+        for row in y0..y0 + SCALE {
+            let start = row * width + x0;
+            y_plane[start..start + SCALE].fill(y as u8);
+            u_plane[start..start + SCALE].fill(u as u8);
+            v_plane[start..start + SCALE].fill(v as u8);
+        }
+    }
+    planes
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Straight from the GIF spec, to check the encoder against
+    fn unlzw(data: &[u8], bits: u32) -> Vec<u8> {
+        let clear = 1u32 << bits;
+        let mut table: Vec<Vec<u8>> = Vec::new();
+        let reset = |table: &mut Vec<Vec<u8>>| {
+            *table = (0..clear).map(|i| vec![i as u8]).collect();
+            table.extend([vec![], vec![]]);
+        };
+        reset(&mut table);
+        let (mut pos, mut width) = (0usize, bits + 1);
+        let mut prev: Option<Vec<u8>> = None;
+        let mut out = Vec::new();
+        loop {
+            let code = (0..width).fold(0, |c, b| {
+                let bit = (data[(pos + b as usize) / 8] >> ((pos + b as usize) % 8)) & 1;
+                c | (bit as u32) << b
+            });
+            pos += width as usize;
+            if code == clear {
+                reset(&mut table);
+                width = bits + 1;
+                prev = None;
+                continue;
+            }
+            if code == clear + 1 {
+                return out;
+            }
+            let entry = match (table.get(code as usize), prev.as_ref()) {
+                (Some(entry), _) => entry.clone(),
+                (None, Some(prev)) => [prev.as_slice(), &prev[..1]].concat(),
+                (None, None) => panic!("bad code {}", code),
+            };
+            if let Some(prev) = prev
+                && table.len() < 4096
+            {
+                table.push([prev.as_slice(), &entry[..1]].concat());
+            }
+            if table.len() == 1 << width && width < 12 {
+                width += 1;
+            }
+            out.extend(&entry);
+            prev = Some(entry);
+        }
+    }
+
+    #[test]
+    fn lzw_round_trips() {
+        let runs: Vec<u8> = (0..20000).map(|i| (i / 37 % 3) as u8).collect();
+        assert_eq!(unlzw(&lzw(&runs, 2), 2), runs);
+        // noise fills the table up and clears it a few times
+        let noise: Vec<u8> = (0..50000u32).map(|i| (i.wrapping_mul(2654435761) >> 24) as u8).collect();
+        assert_eq!(unlzw(&lzw(&noise, 8), 8), noise);
+        assert_eq!(unlzw(&lzw(&[], 2), 2), []);
+    }
+
+    #[test]
+    fn palettes_fit_in_a_byte() {
+        let colors: Vec<u32> = (0..(WIDTH * HEIGHT) as u32).map(|i| i * 0x010203).collect();
+        let (table, indices) = palettize(&colors, (0, 0, WIDTH, HEIGHT));
+        assert!(table.len() <= 256);
+        assert_eq!(indices.len(), WIDTH * HEIGHT);
+        let (table, indices) = palettize(&[0x112233; WIDTH * HEIGHT], (2, 3, 4, 5));
+        assert_eq!((table, indices), (vec![0x112233], vec![0; 20]));
+    }
+
+    #[test]
+    fn gif_merges_frames() {
+        let (black, white, red) = (vec![0; WIDTH * HEIGHT], vec![0xFFFFFF; WIDTH * HEIGHT], vec![0xFF0000; WIDTH * HEIGHT]);
+        let mut recorder = Recorder::new(Vec::new(), Format::Gif).unwrap();
+        recorder.frame(&black, 30).unwrap();
+        recorder.frame(&white, 15).unwrap();
+        recorder.frame(&white, 16).unwrap();
+        // under a hundredth of a second here, red goes up in its place
+        recorder.frame(&black, 1).unwrap();
+        recorder.frame(&red, 58).unwrap();
+        assert_eq!(recorder.frames, 120);
+        let gif = recorder.finish().unwrap();
+        assert!(gif.starts_with(b"GIF89a") && gif.ends_with(&[0x3B]));
+        let controls: Vec<u16> = gif
+            .windows(8)
+            .filter(|w| w[..4] == [0x21, 0xF9, 0x04, 0x04])
+            .map(|w| u16::from_le_bytes([w[4], w[5]]))
+            .collect();
+        assert_eq!(controls, [50, 52, 98]);
+        assert_eq!(delay(0, 7) + delay(7, 60), 100);
+    }
+
+    #[test]
+    fn y4m_repeats_frames() {
+        let mut recorder = Recorder::new(Vec::new(), Format::Y4m).unwrap();
+        recorder.frame(&[0xFFFFFF; WIDTH * HEIGHT], 3).unwrap();
+        let y4m = recorder.finish().unwrap();
+        let header = b"YUV4MPEG2 W256 H128 F60:1 Ip A1:1 C444\n";
+        assert!(y4m.starts_with(header));
+        let frame = 6 + WIDTH * HEIGHT * SCALE * SCALE * 3;
+        assert_eq!(y4m.len(), header.len() + 3 * frame);
+        // white is studio range 235, no colour
+        assert_eq!(y4m[header.len() + 6..][..3], [235, 235, 235]);
+        assert_eq!(y4m[y4m.len() - 1], 128);
+    }
+}
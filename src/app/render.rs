@@ -0,0 +1,244 @@
+// Software scaling of the CHIP-8 screen into Xrgb8888 buffers
+use super::filter::{self, Masks};
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum Scaling {
+    /// Largest whole multiple that fits, crisp pixels
+    #[default]
+    Integer,
+    /// Fill as much as the aspect ratio allows
+    Fractional,
+}
+
+impl Scaling {
+    pub const ALL: &[Scaling] = &[Self::Integer, Self::Fractional];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Integer => "integer",
+            Self::Fractional => "fractional",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Rect {
+    pub x: i32,
+    pub y: i32,
+    pub width: i32,
+    pub height: i32,
+}
+
+impl Rect {
+    pub fn contains(&self, x: i32, y: i32) -> bool {
+        x >= self.x && y >= self.y && x < self.x + self.width && y < self.y + self.height
+    }
+
+    // Empty when they don't overlap
+    pub fn intersect(&self, other: &Rect) -> Rect {
+        let x = self.x.max(other.x);
+        let y = self.y.max(other.y);
+        let right = (self.x + self.width).min(other.x + other.width);
+        let bottom = (self.y + self.height).min(other.y + other.height);
+        Rect { x, y, width: (right - x).max(0), height: (bottom - y).max(0) }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.width <= 0 || self.height <= 0
+    }
+}
+
+// `region` of the `src_w`x`src_h` image to the canvas pixels showing it,
+// rounded outwards so nothing it touches is left out
+pub fn scale_rect(region: Rect, src_w: usize, src_h: usize, dst: Rect) -> Rect {
+    let (src_w, src_h) = (src_w as i32, src_h as i32);
+    let x0 = region.x * dst.width / src_w;
+    let y0 = region.y * dst.height / src_h;
+    let x1 = ((region.x + region.width) * dst.width + src_w - 1) / src_w;
+    let y1 = ((region.y + region.height) * dst.height + src_h - 1) / src_h;
+    Rect { x: dst.x + x0, y: dst.y + y0, width: x1 - x0, height: y1 - y0 }
+}
+
+// Centred `src_w`x`src_h` image scaled into `width`x`height`, keeping the
+// aspect ratio. Integer scaling falls back to fractional when even 1x
+// doesn't fit.
+pub fn fit(scaling: Scaling, width: i32, height: i32, src_w: usize, src_h: usize) -> Rect {
+    let (src_w, src_h) = (src_w as i32, src_h as i32);
+    let scale = (width / src_w).min(height / src_h);
+    let (w, h) = match scaling {
+        Scaling::Integer if scale >= 1 => (src_w * scale, src_h * scale),
+        _ => {
+            // whichever side runs out first decides
+            if width * src_h <= height * src_w {
+                (width, width * src_h / src_w)
+            } else {
+                (height * src_w / src_h, height)
+            }
+        }
+    };
+    Rect {
+        x: (width - w) / 2,
+        y: (height - h) / 2,
+        width: w.max(1),
+        height: h.max(1),
+    }
+}
+
+// Xrgb8888 pixels, rows packed back to back
+pub struct Canvas<'a> {
+    pub pixels: &'a mut [u32],
+    pub width: usize,
+    pub height: usize,
+}
+
+// The emulated screen, already in colour
+pub struct Source<'a> {
+    pub colors: &'a [u32],
+    pub width: usize,
+    pub height: usize,
+}
+
+// Draws `src` into `dst` and fills the rest of the canvas with `bg`, only
+// touching pixels inside `clip`. `masks` has to match the canvas and `dst`.
+pub fn draw(src: &Source<'_>, canvas: &mut Canvas<'_>, dst: Rect, bg: u32, clip: Rect, masks: Option<&Masks>) {
+    let (width, height) = (canvas.width, canvas.height);
+    let clip = clip.intersect(&Rect { x: 0, y: 0, width: width as i32, height: height as i32 });
+    if clip.is_empty() {
+        return;
+    }
+    let (cx0, cy0) = (clip.x as usize, clip.y as usize);
+    let (cx1, cy1) = ((clip.x + clip.width) as usize, (clip.y + clip.height) as usize);
+    let x0 = (dst.x.max(0) as usize).clamp(cx0, cx1);
+    let y0 = (dst.y.max(0) as usize).clamp(cy0, cy1);
+    let x1 = ((dst.x + dst.width).max(0) as usize).clamp(x0, cx1);
+    let y1 = ((dst.y + dst.height).max(0) as usize).clamp(y0, cy1);
+
+    // source column for every destination column inside the image
+    let columns: Vec<usize> = (x0..x1)
+        .map(|x| (x as i32 - dst.x) as usize * src.width / dst.width as usize)
+        .collect();
+
+    // last row drawn, its source row and brightness
+    let mut last: Option<(usize, usize, u32)> = None;
+    for y in cy0..cy1 {
+        let start = y * width;
+        if y < y0 || y >= y1 {
+            canvas.pixels[start + cx0..start + cx1].fill(bg);
+            continue;
+        }
+        let src_y = (y as i32 - dst.y) as usize * src.height / dst.height as usize;
+        let factor = masks.map_or(filter::ONE, |m| m.rows[y]);
+
+        canvas.pixels[start + cx0..start + x0].fill(bg);
+        canvas.pixels[start + x1..start + cx1].fill(bg);
+        // scaled up rows repeat, copying beats redoing them
+        if let Some((last_y, last_src_y, last_factor)) = last
+            && (last_src_y, last_factor) == (src_y, factor)
+        {
+            let from = last_y * width;
+            canvas.pixels.copy_within(from + x0..from + x1, start + x0);
+            continue;
+        }
+
+        let src_row = &src.colors[src_y * src.width..][..src.width];
+        let row = &mut canvas.pixels[start + x0..start + x1];
+        match masks {
+            None => {
+                for (pix, src_x) in row.iter_mut().zip(&columns) {
+                    *pix = src_row[*src_x];
+                }
+            }
+            Some(masks) => {
+                let row_masks = &masks.columns[x0..x1];
+                for ((pix, src_x), column) in row.iter_mut().zip(&columns).zip(row_masks) {
+                    *pix = filter::shade(src_row[*src_x], factor * column / filter::ONE);
+                }
+            }
+        }
+        last = Some((y, src_y, factor));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fit_integer_letterboxes() {
+        let r = fit(Scaling::Integer, 700, 400, 64, 32);
+        assert_eq!(r, Rect { x: 30, y: 40, width: 640, height: 320 });
+        let r = fit(Scaling::Integer, 640, 1000, 64, 32);
+        assert_eq!(r, Rect { x: 0, y: 340, width: 640, height: 320 });
+    }
+
+    #[test]
+    fn fit_fractional_keeps_aspect() {
+        let r = fit(Scaling::Fractional, 700, 400, 64, 32);
+        assert_eq!(r, Rect { x: 0, y: 25, width: 700, height: 350 });
+        let r = fit(Scaling::Fractional, 900, 300, 64, 32);
+        assert_eq!(r, Rect { x: 150, y: 0, width: 600, height: 300 });
+        // below 1x integer gives up
+        assert_eq!(fit(Scaling::Integer, 40, 40, 64, 32), fit(Scaling::Fractional, 40, 40, 64, 32));
+    }
+
+    #[test]
+    fn draw_fills_borders() {
+        let mut fb = [0u32; 4 * 2];
+        fb[0] = 1;
+        fb[3] = 3;
+        let src = Source { colors: &fb, width: 4, height: 2 };
+        let mut pixels = [7u32; 10 * 4];
+        let mut canvas = Canvas { pixels: &mut pixels, width: 10, height: 4 };
+        let dst = fit(Scaling::Integer, 10, 4, 4, 2);
+        let full = Rect { x: 0, y: 0, width: 10, height: 4 };
+        draw(&src, &mut canvas, dst, 0, full, None);
+        #[rustfmt::skip]
+        assert_eq!(pixels, [
+            0, 1, 1, 0, 0, 0, 0, 3, 3, 0,
+            0, 1, 1, 0, 0, 0, 0, 3, 3, 0,
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+            0, 0, 0, 0, 0, 0, 0, 0, 0, 0,
+        ]);
+    }
+
+    #[test]
+    fn scale_rect_rounds_outwards() {
+        let dst = Rect { x: 5, y: 0, width: 100, height: 50 };
+        assert_eq!(scale_rect(Rect { x: 1, y: 1, width: 2, height: 1 }, 64, 32, dst), Rect { x: 6, y: 1, width: 4, height: 3 });
+        let whole = Rect { x: 0, y: 0, width: 64, height: 32 };
+        assert_eq!(scale_rect(whole, 64, 32, dst), dst);
+    }
+
+    #[test]
+    fn draw_only_touches_clip() {
+        let fb = [1u32; 4 * 2];
+        let src = Source { colors: &fb, width: 4, height: 2 };
+        let mut pixels = [7u32; 10 * 4];
+        let mut canvas = Canvas { pixels: &mut pixels, width: 10, height: 4 };
+        let dst = fit(Scaling::Integer, 10, 4, 4, 2);
+        draw(&src, &mut canvas, dst, 0, Rect { x: 0, y: 1, width: 3, height: 2 }, None);
+        #[rustfmt::skip]
+        assert_eq!(pixels, [
+            7, 7, 7, 7, 7, 7, 7, 7, 7, 7,
+            0, 1, 1, 7, 7, 7, 7, 7, 7, 7,
+            0, 1, 1, 7, 7, 7, 7, 7, 7, 7,
+            7, 7, 7, 7, 7, 7, 7, 7, 7, 7,
+        ]);
+    }
+
+    #[test]
+    fn masks_shade_the_image_only() {
+        let fb = [0xFFFFFF_u32; 4 * 2];
+        let src = Source { colors: &fb, width: 4, height: 2 };
+        let mut pixels = [7u32; 14 * 6];
+        let mut canvas = Canvas { pixels: &mut pixels, width: 14, height: 6 };
+        let dst = fit(Scaling::Integer, 14, 6, 4, 2);
+        let full = Rect { x: 0, y: 0, width: 14, height: 6 };
+        let masks = Masks::new(&[filter::Filter::Scanlines], 14, 6, dst, 4, 2).unwrap();
+        draw(&src, &mut canvas, dst, 0, full, Some(&masks));
+        let column: Vec<u32> = (0..6).map(|y| pixels[y * 14 + 5]).collect();
+        let dark = filter::shade(0xFFFFFF, masks.rows[2]);
+        assert_eq!(column, [0xFFFFFF, 0xFFFFFF, dark, 0xFFFFFF, 0xFFFFFF, dark]);
+        assert!((0..6).all(|y| pixels[y * 14] == 0 && pixels[y * 14 + 13] == 0));
+    }
+}
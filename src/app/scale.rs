@@ -0,0 +1,67 @@
+// HiDPI. Buffers are drawn in device pixels and the viewport maps them onto
+// the logical window size, so every buffer pixel lands on exactly one
+// screen pixel and integer scaling stays crisp. Scales are kept in 120ths
+// like wp_fractional_scale_v1 has them.
+use super::*;
+
+/// 1x
+pub(crate) const SCALE_ONE: u32 = 120;
+
+impl App {
+    #[rustfmt::skip]
+    pub(crate) fn on_fractional_scale(&mut self, conn: &Connection, event: WlEvent<'_>) {
+        let Some(fractional_scale) = self.fractional_scale.as_ref() else {
+            return;
+        };
+        match fractional_scale.parse_event(conn.reader(), event) {
+            wp_fractional_scale_v1::Event::PreferredScale { scale } => {
+                if self.preferred_scale != Some(scale) {
+                    self.preferred_scale = Some(scale);
+                    // new buffer size, present() reallocates
+                    self.chip8.need_redraw = true;
+                }
+            },
+        }
+    }
+
+    // Device pixels per logical pixel. Fractional scales need the viewport,
+    // without one it's whole multiples through wl_surface.set_buffer_scale.
+    pub(crate) fn scale120(&self) -> u32 {
+        match (self.viewport.as_ref(), self.preferred_scale) {
+            (Some(_), Some(scale)) => scale,
+            _ => self.buffer_scale as u32 * SCALE_ONE,
+        }
+    }
+
+    // Logical size to device pixels, rounded half away from zero as the
+    // fractional scale protocol asks
+    pub(crate) fn to_device(&self, v: i32) -> i32 {
+        to_device(v, self.scale120())
+    }
+
+    // Surface coordinates of the pointer or a touch to buffer pixels
+    pub(crate) fn to_buffer(&self, x: f64, y: f64) -> (i32, i32) {
+        let scale = self.scale120() as f64 / SCALE_ONE as f64;
+        ((x * scale).floor() as i32, (y * scale).floor() as i32)
+    }
+}
+
+pub(crate) fn to_device(v: i32, scale120: u32) -> i32 {
+    let v = v as i64 * scale120 as i64;
+    ((v + v.signum() * (SCALE_ONE as i64 / 2)) / SCALE_ONE as i64) as i32
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn device_pixels_round_half_away() {
+        assert_eq!(to_device(640, SCALE_ONE), 640);
+        assert_eq!(to_device(640, 180), 960);
+        // 1.25x
+        assert_eq!(to_device(333, 150), 416);
+        assert_eq!(to_device(2, 150), 3);
+        assert_eq!(to_device(-2, 150), -3);
+    }
+}
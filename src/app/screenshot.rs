@@ -0,0 +1,168 @@
+// Screenshots of the emulated screen at the size and in the colours it's
+// shown with, saved as PNG or put on the clipboard. Window decorations,
+// the keypad and filters aren't part of them.
+use std::io::Write;
+use std::os::fd::{FromRawFd, IntoRawFd, OwnedFd};
+use std::path::{Path, PathBuf};
+
+use super::*;
+
+const PNG: &str = "image/png";
+
+impl App {
+    fn screenshot(&self) -> Vec<u8> {
+        let content = self.content_area(self.to_device(self.win_width), self.to_device(self.win_height));
+        let (dst, _) = self.picture_rect(content);
+        let rect = render::Rect { x: 0, y: 0, width: dst.width, height: dst.height };
+        let mut pixels = vec![0; (rect.width * rect.height) as usize];
+        let mut canvas = render::Canvas {
+            pixels: &mut pixels,
+            width: rect.width as usize,
+            height: rect.height as usize,
+        };
+        let src = render::Source {
+            colors: &self.display.colors,
+            width: chip8::WIDTH,
+            height: chip8::HEIGHT,
+        };
+        render::draw(&src, &mut canvas, rect, 0, rect, None);
+        png::encode(&pixels, rect.width as usize, rect.height as usize)
+    }
+
+    pub(crate) fn save_screenshot(&mut self) {
+        let png = self.screenshot();
+        let dir = user_dir_or_home("XDG_PICTURES_DIR");
+        let res = create_unique(&dir, &self.rom_name(), "png").and_then(|(mut file, path)| {
+            file.write_all(&png)?;
+            Ok(path)
+        });
+        match res {
+            Ok(path) => {
+                log!(DEBUG, "Saved '{}'", path.display());
+                self.osd.toast(format!("Saved {}", path.file_name().unwrap_or_default().to_string_lossy()));
+            }
+            Err(e) => {
+                log!(ERR, "Screenshot - {}", e);
+                self.osd.toast("Screenshot failed");
+            }
+        }
+    }
+
+    // Offers the screenshot until something else takes the clipboard
+    pub(crate) fn copy_screenshot(&mut self, conn: &Connection) {
+        let (Some(mgr), Some(data_device)) = (self.data_device_mgr.as_ref(), self.data_device.as_ref()) else {
+            self.osd.toast("No clipboard");
+            return;
+        };
+        let png = self.screenshot();
+        let source = mgr.create_data_source(conn.writer());
+        source.offer(conn.writer(), PNG);
+        data_device.set_selection(conn.writer(), Some(&source), self.key_serial);
+        if let Some((old, _)) = self.clipboard.take() {
+            old.destroy(conn.writer());
+        }
+        self.add_callback(source.id(), Self::on_data_source);
+        self.clipboard = Some((source, png));
+        self.osd.toast("Copied screenshot");
+    }
+
+    #[rustfmt::skip]
+    pub(crate) fn on_data_source(&mut self, conn: &Connection, event: WlEvent<'_>) {
+        let id = event.header.id;
+        let Some((source, png)) = self.clipboard.as_ref().filter(|(s, _)| s.id() == id) else {
+            return;
+        };
+        match source.parse_event(conn.reader(), event) {
+            // PNG is all it offers
+            wl_data_source::Event::Send { mime_type, fd } => {
+                let mut file = std::fs::File::from(unsafe { OwnedFd::from_raw_fd(fd.into_raw_fd()) });
+                let png = png.clone();
+                // the other end reads at its own pace, emulation goes on
+                std::thread::spawn(move || {
+                    if let Err(e) = file.write_all(&png) {
+                        log!(WARNING, "Clipboard - {}", e);
+                    }
+                });
+            },
+            // someone else's now
+            wl_data_source::Event::Cancelled => {
+                source.destroy(conn.writer());
+                self.clipboard = None;
+            },
+            _ => {},
+        }
+    }
+}
+
+// A user directory like `XDG_PICTURES_DIR`, the home directory without one
+pub(crate) fn user_dir_or_home(name: &str) -> PathBuf {
+    let home = std::env::var("HOME").unwrap_or_default();
+    if let Ok(dir) = std::env::var(name)
+        && !dir.is_empty()
+    {
+        return PathBuf::from(dir);
+    }
+    let config = match std::env::var("XDG_CONFIG_HOME") {
+        Ok(dir) if !dir.is_empty() => PathBuf::from(dir),
+        _ => Path::new(&home).join(".config"),
+    };
+    std::fs::read_to_string(config.join("user-dirs.dirs"))
+        .ok()
+        .and_then(|dirs| user_dir(&dirs, name, &home))
+        .unwrap_or_else(|| PathBuf::from(home))
+}
+
+// `name` out of user-dirs.dirs, `XDG_PICTURES_DIR="$HOME/Pictures"` lines
+fn user_dir(dirs: &str, name: &str, home: &str) -> Option<PathBuf> {
+    dirs.lines()
+        .filter(|l| !l.trim_start().starts_with('#'))
+        .filter_map(|l| l.split_once('='))
+        .find(|(key, _)| key.trim() == name)
+        .map(|(_, value)| value.trim().trim_matches('"').replacen("$HOME", home, 1))
+        .map(PathBuf::from)
+}
+
+// New `<rom>-<unix time>.<ext>` in `dir`, numbered when there's more than
+// one a second
+pub(crate) fn create_unique(dir: &Path, rom_name: &str, ext: &str) -> std::io::Result<(std::fs::File, PathBuf)> {
+    let secs = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let stem = format!("{}-{}", rom_name, secs);
+    std::fs::create_dir_all(dir)?;
+    for n in 1.. {
+        let path = match n {
+            1 => dir.join(format!("{}.{}", stem, ext)),
+            _ => dir.join(format!("{}-{}.{}", stem, n, ext)),
+        };
+        match std::fs::File::create_new(&path) {
+            Ok(file) => return Ok((file, path)),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    unreachable!()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn user_dirs() {
+        let dirs = "# written by xdg-user-dirs-update\nXDG_DESKTOP_DIR=\"$HOME/Desktop\"\nXDG_PICTURES_DIR=\"$HOME/Bilder\"\n";
+        assert_eq!(user_dir(dirs, "XDG_PICTURES_DIR", "/home/me"), Some(PathBuf::from("/home/me/Bilder")));
+        assert_eq!(user_dir("XDG_PICTURES_DIR=\"/srv/pics\"", "XDG_PICTURES_DIR", "/home/me"), Some(PathBuf::from("/srv/pics")));
+        assert_eq!(user_dir("#XDG_PICTURES_DIR=\"$HOME/x\"", "XDG_PICTURES_DIR", "/home/me"), None);
+    }
+
+    #[test]
+    fn saves_dont_overwrite() {
+        let dir = std::env::temp_dir().join(format!("eightpotatochips-shots-{}", std::process::id()));
+        let (_, first) = create_unique(&dir, "pong", "png").unwrap();
+        let (_, second) = create_unique(&dir, "pong", "png").unwrap();
+        assert_ne!(first, second);
+        assert!(first.to_string_lossy().ends_with(".png"));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
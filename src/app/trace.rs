@@ -0,0 +1,309 @@
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+use super::chip8::Chip8;
+use super::options::parse_num;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Trigger {
+    Cycle(u64),
+    Pc(usize),
+}
+
+impl Trigger {
+    // `cycle=1000` or `pc=0x2A0`
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s.split_once('=') {
+            Some(("cycle", n)) => Ok(Self::Cycle(parse_num(n)?)),
+            Some(("pc", n)) => Ok(Self::Pc(parse_num(n)? as usize)),
+            _ => Err(format!("'{}' is not a valid trigger, expected cycle=<n> or pc=<addr>", s)),
+        }
+    }
+
+    fn hit(&self, chip8: &Chip8) -> bool {
+        match *self {
+            Self::Cycle(n) => chip8.cycles == n,
+            Self::Pc(addr) => chip8.program_counter == addr,
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub enum TraceFormat {
+    /// Cycle, mnemonic and all of the registers, for diffing two runs of
+    /// this emulator
+    #[default]
+    Native,
+    /// Only the state any interpreter has, for diffing against another one
+    Reference,
+}
+
+impl TraceFormat {
+    pub const ALL: &[TraceFormat] = &[Self::Native, Self::Reference];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::Native => "native",
+            Self::Reference => "reference",
+        }
+    }
+}
+
+#[derive(Debug, Default, Clone)]
+pub struct TraceOptions {
+    pub path: String,
+    pub format: TraceFormat,
+    /// inclusive PC range
+    pub range: Option<(usize, usize)>,
+    pub start: Option<Trigger>,
+    pub stop: Option<Trigger>,
+}
+
+/// Writes one line per executed instruction, before it is executed. The
+/// native format:
+///
+/// `000042 PC:0228 OP:D01F DRW V0, V1, 0xF       V0:0C V1:08 .. VF:00 I:2EA SP:1 DT:00 ST:00`
+///
+/// Everything is fixed width upper case hex (except the decimal cycle number)
+/// so two runs, say two backends or two builds, can be `diff`ed line by line.
+///
+/// CHIP-8 has no standard trace format, so the reference format keeps what
+/// every interpreter can log and leaves out what they disagree on: cycle
+/// counts, mnemonics, the stack pointer's convention and the timers, which
+/// depend on how instructions are paced against 60Hz:
+///
+/// `PC:0228 OP:D01F I:02EA V:0C 08 .. 00`
+///
+/// `I` takes four digits for interpreters that address more than 4K.
+#[derive(Debug)]
+pub(crate) struct Tracer {
+    out: BufWriter<File>,
+    opts: TraceOptions,
+    active: bool,
+    stopped: bool,
+}
+
+impl Tracer {
+    pub fn new(opts: &TraceOptions) -> std::io::Result<Self> {
+        let out = BufWriter::new(File::create(&opts.path)?);
+        Ok(Self {
+            out,
+            active: opts.start.is_none(),
+            stopped: false,
+            opts: opts.clone(),
+        })
+    }
+
+    pub fn log(&mut self, chip8: &Chip8) -> std::io::Result<()> {
+        if self.stopped {
+            return Ok(());
+        }
+        if !self.active && self.opts.start.is_some_and(|t| t.hit(chip8)) {
+            self.active = true;
+        }
+        if self.active && self.opts.stop.is_some_and(|t| t.hit(chip8)) {
+            self.active = false;
+            self.stopped = true;
+            return self.out.flush();
+        }
+        if !self.active {
+            return Ok(());
+        }
+
+        let pc = chip8.program_counter;
+        if self.opts.range.is_some_and(|(lo, hi)| !(lo..=hi).contains(&pc)) {
+            return Ok(());
+        }
+
+        let opcode = chip8.peek_opcode();
+        let out = &mut self.out;
+        if self.opts.format == TraceFormat::Reference {
+            write!(out, "PC:{:04X} OP:{:04X} I:{:04X} V:", pc, opcode, chip8.index)?;
+            for (i, v) in chip8.registers.iter().enumerate() {
+                write!(out, "{}{:02X}", if i == 0 { "" } else { " " }, v)?;
+            }
+            return writeln!(out);
+        }
+        write!(out, "{:06} PC:{:04X} OP:{:04X} {:<18}", chip8.cycles, pc, opcode, mnemonic(opcode))?;
+        for (i, v) in chip8.registers.iter().enumerate() {
+            write!(out, " V{:X}:{:02X}", i, v)?;
+        }
+        writeln!(
+            out,
+            " I:{:03X} SP:{:X} DT:{:02X} ST:{:02X}",
+            chip8.index, chip8.stack_pointer, chip8.delay_timer, chip8.sound_timer
+        )
+    }
+}
+
+// Cowgod's mnemonics, same naming as the instruction methods in `Chip8`
+pub fn mnemonic(opcode: u16) -> String {
+    let x = (opcode & 0x0F00) >> 8;
+    let y = (opcode & 0x00F0) >> 4;
+    let n = opcode & 0x000F;
+    let kk = opcode & 0x00FF;
+    let addr = opcode & 0x0FFF;
+
+    match (opcode & 0xF000) >> 12 {
+        0x0 => match kk {
+            0xE0 => "CLS".into(),
+            0xEE => "RET".into(),
+            _ => format!("SYS 0x{:03X}", addr),
+        },
+        0x1 => format!("JP 0x{:03X}", addr),
+        0x2 => format!("CALL 0x{:03X}", addr),
+        0x3 => format!("SE V{:X}, 0x{:02X}", x, kk),
+        0x4 => format!("SNE V{:X}, 0x{:02X}", x, kk),
+        0x5 => format!("SE V{:X}, V{:X}", x, y),
+        0x6 => format!("LD V{:X}, 0x{:02X}", x, kk),
+        0x7 => format!("ADD V{:X}, 0x{:02X}", x, kk),
+        0x8 => match n {
+            0x0 => format!("LD V{:X}, V{:X}", x, y),
+            0x1 => format!("OR V{:X}, V{:X}", x, y),
+            0x2 => format!("AND V{:X}, V{:X}", x, y),
+            0x3 => format!("XOR V{:X}, V{:X}", x, y),
+            0x4 => format!("ADD V{:X}, V{:X}", x, y),
+            0x5 => format!("SUB V{:X}, V{:X}", x, y),
+            0x6 => format!("SHR V{:X}", x),
+            0x7 => format!("SUBN V{:X}, V{:X}", x, y),
+            0xE => format!("SHL V{:X}", x),
+            _ => format!("DW 0x{:04X}", opcode),
+        },
+        0x9 => format!("SNE V{:X}, V{:X}", x, y),
+        0xA => format!("LD I, 0x{:03X}", addr),
+        0xB => format!("JP V0, 0x{:03X}", addr),
+        0xC => format!("RND V{:X}, 0x{:02X}", x, kk),
+        0xD => format!("DRW V{:X}, V{:X}, 0x{:X}", x, y, n),
+        0xE => match kk {
+            0x9E => format!("SKP V{:X}", x),
+            0xA1 => format!("SKNP V{:X}", x),
+            _ => format!("DW 0x{:04X}", opcode),
+        },
+        0xF => match kk {
+            0x07 => format!("LD V{:X}, DT", x),
+            0x0A => format!("LD V{:X}, K", x),
+            0x15 => format!("LD DT, V{:X}", x),
+            0x18 => format!("LD ST, V{:X}", x),
+            0x1E => format!("ADD I, V{:X}", x),
+            0x29 => format!("LD F, V{:X}", x),
+            0x33 => format!("LD B, V{:X}", x),
+            0x55 => format!("LD [I], V{:X}", x),
+            0x65 => format!("LD V{:X}, [I]", x),
+            _ => format!("DW 0x{:04X}", opcode),
+        },
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn mnemonics() {
+        let cases = [
+            (0x00E0, "CLS"),
+            (0x00EE, "RET"),
+            (0x0123, "SYS 0x123"),
+            (0x1ABC, "JP 0xABC"),
+            (0x2ABC, "CALL 0xABC"),
+            (0x3A12, "SE VA, 0x12"),
+            (0x4A12, "SNE VA, 0x12"),
+            (0x5AB0, "SE VA, VB"),
+            (0x6A12, "LD VA, 0x12"),
+            (0x7A12, "ADD VA, 0x12"),
+            (0x8AB0, "LD VA, VB"),
+            (0x8AB1, "OR VA, VB"),
+            (0x8AB2, "AND VA, VB"),
+            (0x8AB3, "XOR VA, VB"),
+            (0x8AB4, "ADD VA, VB"),
+            (0x8AB5, "SUB VA, VB"),
+            (0x8AB6, "SHR VA"),
+            (0x8AB7, "SUBN VA, VB"),
+            (0x8ABE, "SHL VA"),
+            (0x8AB8, "DW 0x8AB8"),
+            (0x9AB0, "SNE VA, VB"),
+            (0xA123, "LD I, 0x123"),
+            (0xB123, "JP V0, 0x123"),
+            (0xCA12, "RND VA, 0x12"),
+            (0xDAB5, "DRW VA, VB, 0x5"),
+            (0xEA9E, "SKP VA"),
+            (0xEAA1, "SKNP VA"),
+            (0xEA00, "DW 0xEA00"),
+            (0xFA07, "LD VA, DT"),
+            (0xFA0A, "LD VA, K"),
+            (0xFA15, "LD DT, VA"),
+            (0xFA18, "LD ST, VA"),
+            (0xFA1E, "ADD I, VA"),
+            (0xFA29, "LD F, VA"),
+            (0xFA33, "LD B, VA"),
+            (0xFA55, "LD [I], VA"),
+            (0xFA65, "LD VA, [I]"),
+            (0xFAFF, "DW 0xFAFF"),
+        ];
+        for (opcode, expected) in cases {
+            assert_eq!(mnemonic(opcode), expected, "{:04X}", opcode);
+        }
+    }
+
+    #[test]
+    fn trigger_errors() {
+        assert_eq!(Trigger::parse("cycle=1000"), Ok(Trigger::Cycle(1000)));
+        assert_eq!(Trigger::parse("pc=0x2A0"), Ok(Trigger::Pc(0x2A0)));
+        assert!(Trigger::parse("cycle").is_err());
+        assert!(Trigger::parse("pc=zz").is_err());
+        assert!(Trigger::parse("sp=1").is_err());
+    }
+
+    // Lines logged over `cycles` instructions of a loop that counts V0 up:
+    // 0x200 LD V0, 0x05 / 0x202 ADD V0, 0x01 / 0x204 JP 0x202
+    fn trace(name: &str, opts: TraceOptions, cycles: u64) -> Vec<String> {
+        let path = std::env::temp_dir().join(format!("eightpotatochips-trace-{}-{}", name, std::process::id()));
+        let opts = TraceOptions { path: path.to_string_lossy().into_owned(), ..opts };
+        let mut chip8 = Chip8::new();
+        chip8.load_bytes(&[0x60, 0x05, 0x70, 0x01, 0x12, 0x02]).unwrap();
+        let mut tracer = Tracer::new(&opts).unwrap();
+        for _ in 0..cycles {
+            tracer.log(&chip8).unwrap();
+            chip8.cycle().unwrap();
+        }
+        drop(tracer);
+        let lines = std::fs::read_to_string(&path).unwrap().lines().map(String::from).collect();
+        std::fs::remove_file(&path).unwrap();
+        lines
+    }
+
+    // Cycle and PC of each line
+    fn heads(lines: &[String]) -> Vec<&str> {
+        lines.iter().map(|l| &l[..14]).collect()
+    }
+
+    #[test]
+    fn line_format() {
+        let lines = trace("format", TraceOptions::default(), 2);
+        let zeros: String = (1..16).map(|i| format!(" V{:X}:00", i)).collect();
+        assert_eq!(lines[0], format!("000000 PC:0200 OP:6005 LD V0, 0x05        V0:00{} I:000 SP:0 DT:00 ST:00", zeros));
+        assert_eq!(lines[1], format!("000001 PC:0202 OP:7001 ADD V0, 0x01       V0:05{} I:000 SP:0 DT:00 ST:00", zeros));
+    }
+
+    #[test]
+    fn reference_format() {
+        let opts = TraceOptions { format: TraceFormat::Reference, ..Default::default() };
+        let lines = trace("reference", opts, 3);
+        assert_eq!(lines[0], "PC:0200 OP:6005 I:0000 V:00 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00");
+        assert_eq!(lines[2], "PC:0204 OP:1202 I:0000 V:06 00 00 00 00 00 00 00 00 00 00 00 00 00 00 00");
+    }
+
+    #[test]
+    fn range_and_triggers() {
+        let range = TraceOptions { range: Some((0x204, 0x204)), ..Default::default() };
+        assert_eq!(heads(&trace("range", range, 7)), ["000002 PC:0204", "000004 PC:0204", "000006 PC:0204"]);
+
+        let window = TraceOptions {
+            start: Some(Trigger::Pc(0x204)),
+            stop: Some(Trigger::Cycle(5)),
+            ..Default::default()
+        };
+        assert_eq!(heads(&trace("window", window, 9)), ["000002 PC:0204", "000003 PC:0202", "000004 PC:0204"]);
+    }
+}
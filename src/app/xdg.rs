@@ -1,5 +1,43 @@
 use super::*;
 
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct WindowState {
+    pub activated: bool,
+    pub fullscreen: bool,
+    pub maximized: bool,
+    /// Any edge tiled
+    pub tiled: bool,
+}
+
+impl WindowState {
+    // `states` is a wl_array of u32 xdg_toplevel.state values
+    fn parse(states: &[u8]) -> Self {
+        let mut win_state = Self::default();
+        for state in states.chunks_exact(4) {
+            let state = u32::from_ne_bytes([state[0], state[1], state[2], state[3]]);
+            match state {
+                s if s == xdg_toplevel::State::Activated as u32 => win_state.activated = true,
+                s if s == xdg_toplevel::State::Fullscreen as u32 => win_state.fullscreen = true,
+                s if s == xdg_toplevel::State::Maximized as u32 => win_state.maximized = true,
+                s if s == xdg_toplevel::State::TiledLeft as u32
+                    || s == xdg_toplevel::State::TiledRight as u32
+                    || s == xdg_toplevel::State::TiledTop as u32
+                    || s == xdg_toplevel::State::TiledBottom as u32 =>
+                {
+                    win_state.tiled = true
+                }
+                _ => {}
+            }
+        }
+        win_state
+    }
+
+    // The compositor dictates the size, ours doesn't count
+    pub fn constrained(&self) -> bool {
+        self.fullscreen || self.maximized || self.tiled
+    }
+}
+
 impl App {
     pub(crate) fn on_xdgbase(&mut self, conn: &Connection, event: WlEvent) {
         let Some(xdg_base) = self.xdg_base.take() else {
@@ -20,32 +58,23 @@ impl App {
         match xdg_surface.parse_event(conn.reader(), event) {
             xdg_surface::Event::Configure { serial } => {
                 xdg_surface.ack_configure(conn.writer(), serial);
-                if self.configured {
-                    let wl_surface = self.base_surface.as_ref().unwrap();
-                    if let Some(viewport) = self.viewport.as_ref() {
-                        viewport.set_destination(conn.writer(), self.win_width, self.win_height);
-                    }
-                    xdg_surface.set_window_geometry(conn.writer(), 0, 0, self.win_width, self.win_height);
-                    wl_surface.attach(conn.writer(), self.wl_buffer.as_ref(), 0, 0);
-                    wl_surface.damage_buffer(conn.writer(), 0, 0, self.win_width, self.win_height);
-                    wl_surface.commit(conn.writer());
-                } else {
-                    let wl_surface = self.base_surface.as_ref().unwrap();
-                    if let Some(viewport) = self.viewport.as_ref() {
-                        viewport.set_destination(conn.writer(), self.win_width, self.win_height);
-                    }
-                    wl_surface.attach(conn.writer(), self.wl_buffer.as_ref(), 0, 0);
-                    wl_surface.commit(conn.writer());
-                    self.configured = true;
+                // the whole window, present() moves the picture surface
+                if let Some(viewport) = self.viewport.as_ref() {
+                    viewport.set_destination(conn.writer(), self.win_width, self.win_height);
                 }
+                xdg_surface.set_window_geometry(conn.writer(), 0, 0, self.win_width, self.win_height);
+                self.configured = true;
                 if self.deco_mode_changed {
                     if let Some(toplevel_deco) = self.toplevel_deco.as_ref() {
                         toplevel_deco.set_mode(conn.writer(), *self.deco_mode.as_ref().unwrap() as u32);
                     }
+                    self.deco_mode_changed = false;
                 }
             }
         }
         self.xdg_surface = Some(xdg_surface);
+        // new size or first configure, either way the buffer gets (re)drawn
+        self.present(conn);
     }
 
     #[rustfmt::skip]
@@ -56,10 +85,7 @@ impl App {
 
         match xdg_toplevel.parse_event(conn.reader(), event) {
             xdg_toplevel::Event::Configure { width, height, states } => {
-                if height != 0 && width != 0 {
-                    self.win_width = width;
-                    self.win_height = height;
-                }
+                self.configure_toplevel(width, height, WindowState::parse(states));
             }
             xdg_toplevel::Event::Close => {
                 self.exit = true;
@@ -70,24 +96,87 @@ impl App {
         self.xdg_toplevel = Some(xdg_toplevel);
     }
 
+    pub(crate) fn toggle_fullscreen(&mut self, conn: &Connection) {
+        let Some(xdg_toplevel) = self.xdg_toplevel.as_ref() else {
+            return;
+        };
+        if self.win_state.fullscreen {
+            xdg_toplevel.unset_fullscreen(conn.writer());
+        } else {
+            xdg_toplevel.set_fullscreen(conn.writer(), self.fullscreen_output());
+        }
+    }
+
+    pub(crate) fn toggle_maximized(&mut self, conn: &Connection) {
+        let Some(xdg_toplevel) = self.xdg_toplevel.as_ref() else {
+            return;
+        };
+        if self.win_state.maximized {
+            xdg_toplevel.unset_maximized(conn.writer());
+        } else {
+            xdg_toplevel.set_maximized(conn.writer());
+        }
+    }
+
     #[rustfmt::skip]
     pub(crate) fn on_xdgtoplevel_decoration(&mut self, conn: &Connection, event: WlEvent) {
         let Some(toplevel_deco) = self.toplevel_deco.as_ref() else {
             return;
         };
         match toplevel_deco.parse_event(conn.reader(), event) {
-            zxdg_toplevel_decoration_v1::Event::Configure { mode } => {
-                let mode = u32_to_mode(mode);
-                if self.deco_mode != Some(mode) {
-                    self.deco_mode_changed = true;
-                    self.deco_mode = Some(mode);
-                }
-            },
+            zxdg_toplevel_decoration_v1::Event::Configure { mode } => self.configure_decoration(u32_to_mode(mode)),
         }
 
     }
 }
 
+impl App {
+    // Compositors don't decorate fullscreen windows and `csd()` leaves our
+    // bar out, so the decoration mode stays whatever it is
+    fn configure_toplevel(&mut self, width: i32, height: i32, new: WindowState) {
+        let old = self.win_state;
+        if !old.constrained() && new.constrained() {
+            self.floating_size = (self.win_width, self.win_height);
+        }
+
+        if !new.constrained() && old.constrained() {
+            // back to whatever it was before going fullscreen/maximized
+            (self.win_width, self.win_height) = self.floating_size;
+        } else if height != 0 && width != 0 {
+            self.win_width = width;
+            self.win_height = height;
+        }
+        self.win_state = new;
+    }
+
+    fn configure_decoration(&mut self, mode: zxdg_toplevel_decoration_v1::Mode) {
+        if self.deco_mode != Some(mode) {
+            let had_bar = self.csd();
+            self.deco_mode_changed = true;
+            self.deco_mode = Some(mode);
+            self.title_bar_changed(had_bar);
+        }
+    }
+
+    // Our title bar came or went without the compositor picking a size,
+    // the picture keeps its size
+    fn title_bar_changed(&mut self, had_bar: bool) {
+        if had_bar == self.csd() {
+            return;
+        }
+        if !self.win_state.constrained() {
+            self.win_height += match had_bar {
+                true => -csd::TITLE_H,
+                false => csd::TITLE_H,
+            };
+        }
+        if let Some(buffers) = self.buffers.as_mut() {
+            buffers.damage_all();
+        }
+        self.chip8.need_redraw = true;
+    }
+}
+
 // TODO: do this in the scanner side
 fn u32_to_mode(num: u32) -> zxdg_toplevel_decoration_v1::Mode {
     match num {
@@ -96,3 +185,38 @@ fn u32_to_mode(num: u32) -> zxdg_toplevel_decoration_v1::Mode {
         _ => unreachable!()
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn app(deco_mode: Option<zxdg_toplevel_decoration_v1::Mode>) -> App {
+        let mut app = App::new(wl_registry::WlRegistry::from_id(2), wl_display::WlDisplay::from_id(1));
+        app.deco_mode = deco_mode;
+        app.win_width = 640;
+        app.win_height = 320 + if app.csd() { csd::TITLE_H } else { 0 };
+        app
+    }
+
+    #[test]
+    fn fullscreen_and_back_keeps_the_size() {
+        let fullscreen = WindowState { fullscreen: true, ..Default::default() };
+        // decorated by the compositor, then by us without xdg-decoration
+        for deco_mode in [Some(zxdg_toplevel_decoration_v1::Mode::ServerSide), None] {
+            let mut app = app(deco_mode);
+            let floating = (app.win_width, app.win_height);
+            app.configure_toplevel(1920, 1080, fullscreen);
+            assert_eq!((app.win_width, app.win_height), (1920, 1080));
+            assert!(!app.csd());
+
+            app.configure_toplevel(0, 0, WindowState::default());
+            assert_eq!((app.win_width, app.win_height), floating);
+            // the mode didn't change, so a late decoration configure is a no-op
+            if let Some(mode) = deco_mode {
+                app.configure_decoration(mode);
+            }
+            assert_eq!(app.deco_mode, deco_mode);
+            assert_eq!((app.win_width, app.win_height), floating);
+        }
+    }
+}
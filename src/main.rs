@@ -4,26 +4,48 @@ mod app;
 mod shared;
 
 fn main() -> std::io::Result<()> {
-    let Some(rom_path) = std::env::args().skip(1).next() else {
-        scratchway::log!(ERR, "No rom provided");
-        return Ok(())
+    let opts = match app::Options::from_args(std::env::args().skip(1)) {
+        Ok(opts) => opts,
+        Err(e) => {
+            scratchway::log!(ERR, "{}", e);
+            return Ok(())
+        }
     };
 
+    if let Some(cycles) = opts.bench {
+        if let Err(e) = app::bench::run(&opts, cycles) {
+            scratchway::log!(ERR, "'{}' - {}", opts.rom_path, e);
+        }
+        return Ok(())
+    }
+
+    if let (Some(frames), Some(path)) = (opts.headless, opts.record.as_deref()) {
+        if let Err(e) = app::headless::run(&opts, path, frames) {
+            scratchway::log!(ERR, "'{}' - {}", path, e);
+        }
+        return Ok(())
+    }
+
     let conn = Connection::connect()?;
     let wl_display = conn.display();
     let wl_registry = wl_display.get_registry(conn.writer());
 
     let mut app = app::App::new(wl_registry, wl_display);
 
+    conn.roundtrip(&mut app)?;
+    // wl_output names only come in after the globals are bound
     conn.roundtrip(&mut app)?;
     conn.dispatch_events(&mut app)?;
 
-    if let Err(err) = app.init(&conn, &rom_path) {
+    if let Err(err) = app.init(&conn, &opts) {
         scratchway::log!(ERR, "{}", err);
         return Ok(())
     }
 
-    app.enter_loop(&conn)?;
+    let res = app.enter_loop(&conn);
+    // whatever went wrong, the video should still play
+    app.stop_recording();
+    res?;
     // while !app.exit {
     //     conn.dispatch_events(&mut app)?;
     // }
@@ -0,0 +1,61 @@
+#![allow(unused)]
+/// Anonymous mapping for generated code, kept read+exec except while writing.
+#[derive(Debug)]
+pub(crate) struct ExecMemory {
+    ptr: NonNull<u8>,
+    size: usize,
+}
+
+impl ExecMemory {
+    pub fn new(size: usize) -> std::io::Result<Self> {
+        let data = unsafe {
+            libc::mmap(
+                core::ptr::null_mut(),
+                size,
+                libc::PROT_READ | libc::PROT_EXEC,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            )
+        };
+        if data == libc::MAP_FAILED {
+            return Err(std::io::Error::last_os_error());
+        }
+
+        let ptr = unsafe { NonNull::new_unchecked(data as *mut u8) };
+        Ok(Self { ptr, size })
+    }
+
+    pub fn write(&mut self, offset: usize, code: &[u8]) -> std::io::Result<()> {
+        assert!(offset + code.len() <= self.size);
+        unsafe {
+            self.protect(libc::PROT_READ | libc::PROT_WRITE)?;
+            core::ptr::copy_nonoverlapping(code.as_ptr(), self.ptr.as_ptr().add(offset), code.len());
+            self.protect(libc::PROT_READ | libc::PROT_EXEC)
+        }
+    }
+
+    unsafe fn protect(&self, prot: i32) -> std::io::Result<()> {
+        unsafe { syscall(libc::mprotect(self.ptr.as_ptr().cast(), self.size, prot)) }?;
+        Ok(())
+    }
+
+    pub fn size(&self) -> usize {
+        self.size
+    }
+
+    pub fn as_ptr(&self) -> *const u8 {
+        self.ptr.as_ptr()
+    }
+}
+
+impl Drop for ExecMemory {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr.as_ptr().cast(), self.size);
+        }
+    }
+}
+
+use std::ptr::NonNull;
+use super::syscall;
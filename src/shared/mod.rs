@@ -1,3 +1,4 @@
+pub mod exec;
 pub mod shm;
 pub mod ticker;
 
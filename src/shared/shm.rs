@@ -3,6 +3,8 @@
 pub(crate) struct ShmData {
     ptr: NonNull<u8>,
     size: usize,
+    // kept open until the mapping goes, the compositor gets its own copy
+    fd: OwnedFd,
 }
 
 impl ShmData {
@@ -37,7 +39,9 @@ impl ShmData {
 
         let ptr = unsafe { NonNull::new_unchecked(data as *mut u8) };
 
-        Ok((Self { ptr, size }, fd))
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let raw = fd.as_raw_fd();
+        Ok((Self { ptr, size, fd }, raw))
     }
 
     pub fn as_slice<T>(&self) -> &[T] {
@@ -56,16 +60,13 @@ impl ShmData {
 impl Drop for ShmData {
     fn drop(&mut self) {
         unsafe {
-            libc::munmap(
-                self.ptr.as_ptr().cast(),
-                core::mem::size_of_val(self.ptr.as_mut()),
-            );
+            libc::munmap(self.ptr.as_ptr().cast(), self.size);
         }
     }
 }
 
 use std::{
-    os::fd::{OwnedFd, RawFd},
+    os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd},
     ptr::NonNull,
 };
 use super::{syscall, syscall_ptr};